@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    in_game,
+    loading_plugin::AllAssets,
+    player_plugin::{PlayerFlag, PlayerState, PlayerStateChanged},
+};
+
+/// Horizontal speed below which a sprite keeps facing its previous direction.
+const FLIP_THRESHOLD: f32 = 1.;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AnimationClip {
+    pub first: usize,
+    pub last: usize,
+    pub fps: f32,
+    pub looping: bool,
+}
+
+/// Named clips over a texture atlas, plus which clip each player state should play.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TypeUuid)]
+#[uuid = "a3c1f0d4-7b52-4e2a-8d19-6e4b0c7f9a13"]
+pub struct AnimationLibrary {
+    pub clips: HashMap<String, AnimationClip>,
+    pub states: HashMap<PlayerState, String>,
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct SpriteAnimator {
+    pub library: Handle<AnimationLibrary>,
+    pub clip: String,
+    pub frame: usize,
+    pub timer: f32,
+}
+
+impl SpriteAnimator {
+    pub fn new(library: Handle<AnimationLibrary>, clip: &str) -> Self {
+        Self {
+            library,
+            clip: clip.to_string(),
+            frame: 0,
+            timer: 0.,
+        }
+    }
+
+    pub fn play(&mut self, clip: &str) {
+        if self.clip != clip {
+            self.clip = clip.to_string();
+            self.frame = 0;
+            self.timer = 0.;
+        }
+    }
+}
+
+pub struct AnimationPlugin;
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            (
+                attach_player_animator,
+                select_player_clip,
+                animate_sprites,
+                flip_sprites,
+            )
+                .chain()
+                .distributive_run_if(in_game),
+        );
+    }
+}
+
+fn attach_player_animator(
+    mut commands: Commands,
+    assets: Res<AllAssets>,
+    libraries: Res<Assets<AnimationLibrary>>,
+    player_query: Query<Entity, Added<PlayerFlag>>,
+) {
+    for entity in &player_query {
+        let clip = libraries
+            .get(&assets.kitty_animations)
+            .and_then(|library| library.states.get(&PlayerState::default()))
+            .cloned()
+            .unwrap_or_default();
+        // the LDtk entity tile is a single frame, the clips index into the kitty sheet
+        commands.entity(entity).insert((
+            assets.kitty_sheet.clone(),
+            SpriteAnimator::new(assets.kitty_animations.clone(), &clip),
+        ));
+    }
+}
+
+fn select_player_clip(
+    libraries: Res<Assets<AnimationLibrary>>,
+    mut state_events: EventReader<PlayerStateChanged>,
+    mut animator_query: Query<&mut SpriteAnimator>,
+) {
+    for PlayerStateChanged { entity, to, .. } in state_events.iter() {
+        if let Ok(mut animator) = animator_query.get_mut(*entity) {
+            if let Some(clip) = libraries
+                .get(&animator.library)
+                .and_then(|library| library.states.get(to))
+                .cloned()
+            {
+                animator.play(&clip);
+            }
+        }
+    }
+}
+
+fn animate_sprites(
+    time: Res<Time>,
+    libraries: Res<Assets<AnimationLibrary>>,
+    mut animator_query: Query<(&mut SpriteAnimator, &mut TextureAtlasSprite)>,
+) {
+    for (mut animator, mut sprite) in &mut animator_query {
+        if let Some(clip) = libraries
+            .get(&animator.library)
+            .and_then(|library| library.clips.get(&animator.clip))
+        {
+            let frame_count = clip.last.saturating_sub(clip.first) + 1;
+            if clip.fps > 0. {
+                let frame_time = 1. / clip.fps;
+                animator.timer += time.delta_seconds();
+                while animator.timer >= frame_time {
+                    animator.timer -= frame_time;
+                    animator.frame = if clip.looping {
+                        (animator.frame + 1) % frame_count
+                    } else {
+                        (animator.frame + 1).min(frame_count - 1)
+                    };
+                }
+            }
+            let index = clip.first + animator.frame.min(frame_count - 1);
+            if sprite.index != index {
+                sprite.index = index;
+            }
+        }
+    }
+}
+
+fn flip_sprites(
+    mut sprite_query: Query<(&Velocity, &mut TextureAtlasSprite), With<SpriteAnimator>>,
+) {
+    for (velocity, mut sprite) in &mut sprite_query {
+        if velocity.linvel.x.abs() > FLIP_THRESHOLD {
+            let flip_x = velocity.linvel.x < 0.;
+            if sprite.flip_x != flip_x {
+                sprite.flip_x = flip_x;
+            }
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_kira_audio::{AudioApp, AudioChannel, AudioControl, AudioEasing, AudioTween};
+
+use crate::{
+    in_game,
+    loading_plugin::AllAssets,
+    player_plugin::{MovePerformed, PlayerRespawned, PlayerState, PlayerStateChanged},
+};
+
+const MUSIC_FADE: Duration = Duration::from_millis(500);
+
+#[derive(Resource)]
+pub struct MusicChannel;
+
+#[derive(Resource)]
+pub struct SfxChannel;
+
+#[derive(Resource, Clone, Debug)]
+pub struct AudioVolume {
+    pub master: f64,
+    pub music: f64,
+    pub sfx: f64,
+}
+
+impl Default for AudioVolume {
+    fn default() -> Self {
+        Self {
+            master: 1.,
+            music: 0.5,
+            sfx: 0.8,
+        }
+    }
+}
+
+/// Asset path of the music track currently looping, taken from the level's `Music` field.
+#[derive(Resource, Default)]
+struct CurrentMusic(Option<String>);
+
+pub struct AudioPlugin;
+impl Plugin for AudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(bevy_kira_audio::AudioPlugin)
+            .add_audio_channel::<MusicChannel>()
+            .add_audio_channel::<SfxChannel>()
+            .init_resource::<AudioVolume>()
+            .init_resource::<CurrentMusic>()
+            .add_system(apply_volume)
+            .add_systems(
+                (play_movement_sounds, play_respawn_sound, play_level_music)
+                    .distributive_run_if(in_game),
+            );
+    }
+}
+
+fn apply_volume(
+    volume: Res<AudioVolume>,
+    music_channel: Res<AudioChannel<MusicChannel>>,
+    sfx_channel: Res<AudioChannel<SfxChannel>>,
+) {
+    if volume.is_changed() {
+        music_channel.set_volume(volume.master * volume.music);
+        sfx_channel.set_volume(volume.master * volume.sfx);
+    }
+}
+
+fn play_movement_sounds(
+    assets: Res<AllAssets>,
+    sfx_channel: Res<AudioChannel<SfxChannel>>,
+    mut move_events: EventReader<MovePerformed>,
+    mut state_events: EventReader<PlayerStateChanged>,
+) {
+    for MovePerformed { state, .. } in move_events.iter() {
+        let sound = match state {
+            PlayerState::Jump => &assets.jump_sound,
+            PlayerState::WallJump => &assets.wall_jump_sound,
+            PlayerState::AirJump => &assets.air_jump_sound,
+            _ => continue,
+        };
+        sfx_channel.play(sound.clone());
+    }
+    for PlayerStateChanged { from, to, .. } in state_events.iter() {
+        if *from == PlayerState::GroundPound
+            && matches!(
+                to,
+                PlayerState::Idle | PlayerState::Run | PlayerState::Crouch
+            )
+        {
+            sfx_channel.play(assets.ground_pound_sound.clone());
+        }
+    }
+}
+
+fn play_respawn_sound(
+    assets: Res<AllAssets>,
+    sfx_channel: Res<AudioChannel<SfxChannel>>,
+    mut respawn_events: EventReader<PlayerRespawned>,
+) {
+    for _ in respawn_events.iter() {
+        sfx_channel.play(assets.respawn_sound.clone());
+    }
+}
+
+fn play_level_music(
+    assets: Res<AllAssets>,
+    ldtk_assets: Res<Assets<LdtkAsset>>,
+    level_selection: Res<LevelSelection>,
+    music_channel: Res<AudioChannel<MusicChannel>>,
+    mut current_music: ResMut<CurrentMusic>,
+) {
+    if !level_selection.is_changed() {
+        return;
+    }
+
+    let music = ldtk_assets
+        .get(&assets.level0)
+        .and_then(|ldtk_asset| ldtk_asset.get_level(&level_selection))
+        .and_then(|level| level.get_maybe_string_field("Music").ok().cloned())
+        .flatten();
+
+    if music == current_music.0 {
+        return;
+    }
+
+    music_channel
+        .stop()
+        .fade_out(AudioTween::new(MUSIC_FADE, AudioEasing::OutPowi(2)));
+    if let Some(handle) = music.as_ref().and_then(|path| assets.music.get(path)) {
+        music_channel
+            .play(handle.clone())
+            .looped()
+            .fade_in(AudioTween::new(MUSIC_FADE, AudioEasing::OutPowi(2)));
+    }
+    current_music.0 = music;
+}
@@ -0,0 +1,267 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_ecs_ldtk::utils::grid_coords_to_translation_relative_to_tile_layer;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    config_plugin::{BLOCK_RESPAWN_TIME, CRUMBLE_TIME},
+    in_game,
+    physics_plugin::FixedSet,
+    player_plugin::{player_movement, update_safe_spot},
+    wall_plugin::{collider_extents, ContactDetection, Wall, BREAKABLE_WALL, CRUMBLING_WALL},
+    GameState, PlayerState, PlayerStateChanged,
+};
+
+const CRUMBLING_COLOR: Color = Color::rgb(0.72, 0.56, 0.38);
+const BREAKABLE_COLOR: Color = Color::rgb(0.52, 0.47, 0.44);
+/// How far the bottom of a detector may be from the top of a cell while standing on it.
+const FOOTING_TOLERANCE: f32 = 2.;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum BreakableKind {
+    /// Breaks shortly after being stood on and comes back later.
+    #[default]
+    Crumbling,
+    /// Breaks for good when a ground pound lands on it.
+    GroundPound,
+}
+
+/// Wall cells that can break. Breaking removes the cell's [Wall] component, and restoring the
+/// cell inserts it again, so the merged wall collision around it is rebuilt.
+#[derive(Component, Clone, Debug, Default)]
+pub struct Breakable {
+    pub kind: BreakableKind,
+    /// Seconds until a crumbling cell breaks, or until a broken cell comes back.
+    pub timer: Option<f32>,
+}
+
+impl From<IntGridCell> for Breakable {
+    fn from(int_grid_cell: IntGridCell) -> Breakable {
+        let kind = match int_grid_cell.value {
+            BREAKABLE_WALL => BreakableKind::GroundPound,
+            _ => BreakableKind::Crumbling,
+        };
+        Breakable { kind, timer: None }
+    }
+}
+
+#[derive(Clone, Debug, Default, Bundle, LdtkIntCell)]
+pub struct BreakableBundle {
+    #[from_int_grid_cell]
+    wall: Wall,
+
+    #[from_int_grid_cell]
+    breakable: Breakable,
+}
+
+/// Sprite drawn over a breakable cell, which has no tile of its own.
+#[derive(Component)]
+pub struct BreakableSprite {
+    pub cell: Entity,
+}
+
+pub struct BreakablePlugin;
+
+impl Plugin for BreakablePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_int_cell::<BreakableBundle>(CRUMBLING_WALL)
+            .register_ldtk_int_cell::<BreakableBundle>(BREAKABLE_WALL)
+            .add_systems(
+                (spawn_breakable_sprites, update_breakable_sprites).distributive_run_if(in_game),
+            )
+            .add_systems(
+                (detect_footing, pound_breakables, tick_breakables)
+                    .chain()
+                    .after(player_movement)
+                    .before(update_safe_spot)
+                    .distributive_run_if(in_state(GameState::Playing))
+                    .in_set(FixedSet::Logic)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            );
+    }
+}
+
+fn cell_bounds(
+    grid_coords: GridCoords,
+    layer: &LayerMetadata,
+    layer_transform: &GlobalTransform,
+) -> Rect {
+    let centre = layer_transform.translation().truncate()
+        + grid_coords_to_translation_relative_to_tile_layer(
+            grid_coords,
+            IVec2::splat(layer.grid_size),
+        );
+    Rect::from_center_size(centre, Vec2::splat(layer.grid_size as f32))
+}
+
+fn detector_bounds(translation: Vec3, collider: &Collider) -> Rect {
+    let (centre, half_extents) = collider_extents(collider);
+    Rect::from_center_half_size(translation.truncate() + centre, half_extents)
+}
+
+fn stands_on(detector: Rect, cell: Rect) -> bool {
+    detector.min.x < cell.max.x
+        && detector.max.x > cell.min.x
+        && (detector.min.y - cell.max.y).abs() <= FOOTING_TOLERANCE
+}
+
+fn spawn_breakable_sprites(
+    mut commands: Commands,
+    cell_query: Query<(Entity, &GridCoords, &Parent, &Breakable), Added<Breakable>>,
+    layer_query: Query<&LayerMetadata>,
+) {
+    for (cell, &grid_coords, parent, breakable) in &cell_query {
+        let Ok(layer) = layer_query.get(parent.get()) else {
+            continue;
+        };
+        let translation = grid_coords_to_translation_relative_to_tile_layer(
+            grid_coords,
+            IVec2::splat(layer.grid_size),
+        );
+        commands.entity(parent.get()).with_children(|layer_entity| {
+            layer_entity.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: match breakable.kind {
+                            BreakableKind::Crumbling => CRUMBLING_COLOR,
+                            BreakableKind::GroundPound => BREAKABLE_COLOR,
+                        },
+                        custom_size: Some(Vec2::splat(layer.grid_size as f32)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(translation.extend(0.)),
+                    ..default()
+                },
+                BreakableSprite { cell },
+            ));
+        });
+    }
+}
+
+/// Hides the sprites of broken cells and fades crumbling ones out.
+fn update_breakable_sprites(
+    mut sprite_query: Query<(&BreakableSprite, &mut Sprite, &mut Visibility)>,
+    cell_query: Query<(&Breakable, Option<&Wall>)>,
+) {
+    for (breakable_sprite, mut sprite, mut visibility) in &mut sprite_query {
+        let Ok((breakable, wall)) = cell_query.get(breakable_sprite.cell) else {
+            continue;
+        };
+        visibility.set_if_neq(if wall.is_some() {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+        let alpha = match (wall, breakable.timer) {
+            (Some(_), Some(timer)) => 0.4 + 0.6 * timer / CRUMBLE_TIME,
+            _ => 1.,
+        };
+        if sprite.color.a() != alpha {
+            sprite.color.set_a(alpha);
+        }
+    }
+}
+
+/// Flags detectors standing on breakable cells, and starts crumbling cells under them.
+fn detect_footing(
+    mut detector_query: Query<(&GlobalTransform, &Collider, &mut ContactDetection)>,
+    mut cell_query: Query<(&GridCoords, &Parent, &mut Breakable), With<Wall>>,
+    layer_query: Query<(&LayerMetadata, &GlobalTransform)>,
+) {
+    for (transform, collider, mut contact_detection) in &mut detector_query {
+        let detector = detector_bounds(transform.translation(), collider);
+        let mut on_breakable = false;
+        if contact_detection.on_ground {
+            for (&grid_coords, parent, mut breakable) in &mut cell_query {
+                let Ok((layer, layer_transform)) = layer_query.get(parent.get()) else {
+                    continue;
+                };
+                if stands_on(detector, cell_bounds(grid_coords, layer, layer_transform)) {
+                    on_breakable = true;
+                    if breakable.kind == BreakableKind::Crumbling && breakable.timer.is_none() {
+                        breakable.timer = Some(CRUMBLE_TIME);
+                    }
+                }
+            }
+        }
+        if contact_detection.on_breakable != on_breakable {
+            contact_detection.on_breakable = on_breakable;
+        }
+    }
+}
+
+/// Breaks every breakable cell under a player whose ground pound just hit the ground.
+fn pound_breakables(
+    mut commands: Commands,
+    mut state_events: EventReader<PlayerStateChanged>,
+    player_query: Query<(&Transform, &Collider, &ContactDetection)>,
+    mut cell_query: Query<(Entity, &GridCoords, &Parent, &mut Breakable), With<Wall>>,
+    layer_query: Query<(&LayerMetadata, &GlobalTransform)>,
+) {
+    for event in state_events.iter() {
+        if event.from != PlayerState::GroundPound {
+            continue;
+        }
+        let Ok((transform, collider, contact_detection)) = player_query.get(event.entity) else {
+            continue;
+        };
+        // the global transform is a step behind while falling this fast, the transform is where
+        // the pound landed
+        let detector = detector_bounds(transform.translation, collider);
+        if !contact_detection.on_ground {
+            continue;
+        }
+        for (cell, &grid_coords, parent, mut breakable) in &mut cell_query {
+            let Ok((layer, layer_transform)) = layer_query.get(parent.get()) else {
+                continue;
+            };
+            if stands_on(detector, cell_bounds(grid_coords, layer, layer_transform)) {
+                commands.entity(cell).remove::<Wall>();
+                breakable.timer = match breakable.kind {
+                    BreakableKind::Crumbling => Some(BLOCK_RESPAWN_TIME),
+                    BreakableKind::GroundPound => None,
+                };
+            }
+        }
+    }
+}
+
+/// Breaks crumbling cells and restores broken ones once their timer runs out. A cell is only
+/// restored once nothing is inside it.
+fn tick_breakables(
+    mut commands: Commands,
+    fixed_time: Res<FixedTime>,
+    mut cell_query: Query<(Entity, &GridCoords, &Parent, &mut Breakable, Option<&Wall>)>,
+    layer_query: Query<(&LayerMetadata, &GlobalTransform)>,
+    detector_query: Query<(&GlobalTransform, &Collider), With<ContactDetection>>,
+) {
+    for (cell, &grid_coords, parent, mut breakable, wall) in &mut cell_query {
+        let Some(timer) = breakable.timer.as_mut() else {
+            continue;
+        };
+        *timer -= fixed_time.period.as_secs_f32();
+        if *timer > 0. {
+            continue;
+        }
+
+        if wall.is_some() {
+            commands.entity(cell).remove::<Wall>();
+            breakable.timer = Some(BLOCK_RESPAWN_TIME);
+            continue;
+        }
+
+        let Ok((layer, layer_transform)) = layer_query.get(parent.get()) else {
+            continue;
+        };
+        let bounds = cell_bounds(grid_coords, layer, layer_transform);
+        let occupied = detector_query.iter().any(|(transform, collider)| {
+            !detector_bounds(transform.translation(), collider)
+                .intersect(bounds)
+                .is_empty()
+        });
+        if !occupied {
+            commands.entity(cell).insert(Wall::default());
+            breakable.timer = None;
+        }
+    }
+}
@@ -1,16 +1,130 @@
 use bevy::prelude::*;
 use bevy::render::camera::ScalingMode;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+use rand::Rng;
 
-use crate::{config_plugin::CAMERA_SPEED, player_plugin::PlayerFlag, HEIGHT, WIDTH};
+use crate::{
+    controls_plugin::Paused,
+    in_game,
+    level_plugin::{level_bounds, LevelProgress},
+    physics_plugin::interpolate_transforms,
+    player_plugin::{PlayerDied, PlayerFlag, PlayerState, PlayerStateChanged},
+    replay_plugin::GameRng,
+    wall_plugin::{ContactDetection, Landed},
+    HEIGHT, WIDTH,
+};
+
+/// Horizontal speed below which the look-ahead keeps pointing in the previous direction.
+const FACING_THRESHOLD: f32 = 1.;
+/// Downward speed above which landing shakes the camera.
+const HARD_LANDING_SPEED: f32 = 700.;
 
 #[derive(Component)]
 pub struct CameraFlag;
 
+/// How the camera follows the player, distances are in world units and rates per second.
+#[derive(Resource, Clone, Debug)]
+pub struct CameraConfig {
+    /// Size of the rectangle around the camera focus the player can move in without moving it.
+    /// Vertically it only applies while the player is on the ground.
+    pub dead_zone: Vec2,
+    /// How far the camera looks ahead of the player in the direction they are moving.
+    pub look_ahead: f32,
+    pub look_ahead_rate: f32,
+    /// How far the player can get above or below the camera focus while airborne before it
+    /// starts following them vertically.
+    pub vertical_threshold: f32,
+    /// Rate at which the camera closes in on where it should be.
+    pub smoothing: f32,
+    /// Furthest the camera is moved by a shake at full trauma.
+    pub max_shake: f32,
+    /// How quickly the shake changes direction.
+    pub shake_frequency: f32,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self {
+            dead_zone: Vec2::new(24., 16.),
+            look_ahead: 32.,
+            look_ahead_rate: 2.,
+            vertical_threshold: 48.,
+            smoothing: 3.,
+            max_shake: 6.,
+            shake_frequency: 25.,
+        }
+    }
+}
+
+/// Where the camera is heading, tracked on the camera so it can lag behind the player.
+#[derive(Component, Default)]
+struct CameraFocus {
+    /// Centre of the dead zone, `None` until the player is first seen.
+    position: Option<Vec2>,
+    facing: f32,
+    look_ahead: f32,
+    /// Bounds of the level the player is in, which the shake must not show past either.
+    bounds: Option<Rect>,
+}
+
+/// Adds trauma to the camera, between 0 and 1, which wears off over `duration` seconds. The
+/// shake grows with the square of the trauma, so small amounts barely register.
+pub struct CameraShake {
+    pub intensity: f32,
+    pub duration: f32,
+}
+
+/// Zooms the camera in by a fraction of its view, easing back out over `duration` seconds.
+pub struct ZoomPunch {
+    pub amount: f32,
+    pub duration: f32,
+}
+
+/// Freezes the game for `duration` seconds, while the camera keeps shaking.
+pub struct Hitstop {
+    pub duration: f32,
+}
+
+/// Shake and zoom applied on top of where the camera follows the player to.
+#[derive(Component, Default)]
+pub struct CameraEffects {
+    pub trauma: f32,
+    trauma_decay: f32,
+    pub zoom: f32,
+    zoom_decay: f32,
+    elapsed: f32,
+    /// Picked from the game's random numbers on each shake, so no two shakes look the same.
+    seed: f32,
+    /// Offset added to the camera translation last frame, taken off again before following.
+    offset: Vec2,
+}
+
+/// Seconds of hitstop left, the game time is paused while it runs.
+#[derive(Resource, Default)]
+struct HitstopTimer(f32);
+
 pub struct CameraPlugin;
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(camera_setup)
-            .add_system(camera_follow_system);
+        app.init_resource::<CameraConfig>()
+            .init_resource::<HitstopTimer>()
+            .add_event::<CameraShake>()
+            .add_event::<ZoomPunch>()
+            .add_event::<Hitstop>()
+            .add_startup_system(camera_setup)
+            .add_systems(
+                (
+                    remove_camera_effects,
+                    camera_follow_system,
+                    apply_camera_effects,
+                )
+                    .chain()
+                    .after(interpolate_transforms),
+            )
+            .add_system(send_camera_effects.run_if(in_game))
+            // pauses the time from the next frame on, before its fixed steps run
+            .add_system(hitstop_system.after(send_camera_effects));
     }
 }
 
@@ -24,18 +138,421 @@ fn camera_setup(mut commands: Commands) {
             },
             ..Default::default()
         })
-        .insert(CameraFlag);
+        .insert(CameraFlag)
+        .insert(CameraFocus::default())
+        .insert(CameraEffects::default());
 }
 
+/// Size of the world area shown by the camera without zoom, which is only known once it has been
+/// rendered.
+fn view_size(projection: &OrthographicProjection) -> Vec2 {
+    if projection.area == OrthographicProjection::default().area {
+        Vec2::new(WIDTH, HEIGHT)
+    } else {
+        projection.area.size() / projection.scale
+    }
+}
+
+/// Moves the bottom left corner of a view so that the view stays inside the bounds, or is centred
+/// on them along any axis where they are smaller than the view.
+fn clamp_view(corner: Vec2, view: Vec2, bounds: Rect) -> Vec2 {
+    let clamp_axis = |corner: f32, view: f32, min: f32, max: f32| {
+        if max - min <= view {
+            (min + max - view) / 2.
+        } else {
+            corner.clamp(min, max - view)
+        }
+    };
+    Vec2::new(
+        clamp_axis(corner.x, view.x, bounds.min.x, bounds.max.x),
+        clamp_axis(corner.y, view.y, bounds.min.y, bounds.max.y),
+    )
+}
+
+/// Moves the focus just enough to keep the player inside the dead zone. Vertically that only
+/// happens on the ground, in the air the player can get as far as the vertical threshold away.
+/// Negative sizes in the config count as their magnitude rather than making the clamps panic.
+fn update_focus(focus: Vec2, player: Vec2, on_ground: bool, config: &CameraConfig) -> Vec2 {
+    let half_zone = config.dead_zone.abs() / 2.;
+    let vertical_slack = if on_ground {
+        half_zone.y
+    } else {
+        config.vertical_threshold.abs()
+    };
+    Vec2::new(
+        focus
+            .x
+            .clamp(player.x - half_zone.x, player.x + half_zone.x),
+        focus
+            .y
+            .clamp(player.y - vertical_slack, player.y + vertical_slack),
+    )
+}
+
+/// Fraction of the remaining distance covered in `delta` seconds when closing in at `rate`,
+/// the same however the time is split up into frames.
+fn smoothing_factor(rate: f32, delta: f32) -> f32 {
+    1. - (-rate * delta).exp()
+}
+
+/// Follows the player within the bounds of the level they are in. The bounds only limit where
+/// the camera is heading, so it eases over to a neighbouring level's bounds on entering it.
 fn camera_follow_system(
-    player_query: Query<&Transform, With<PlayerFlag>>,
-    mut camera_query: Query<&mut Transform, (With<CameraFlag>, Without<PlayerFlag>)>,
+    time: Res<Time>,
+    config: Res<CameraConfig>,
+    progress: Res<LevelProgress>,
+    ldtk_levels: Res<Assets<LdtkLevel>>,
+    level_query: Query<(&Handle<LdtkLevel>, &GlobalTransform)>,
+    player_query: Query<(&Transform, &Velocity, &ContactDetection), With<PlayerFlag>>,
+    mut camera_query: Query<
+        (&mut Transform, &mut CameraFocus, &OrthographicProjection),
+        (With<CameraFlag>, Without<PlayerFlag>),
+    >,
+) {
+    let (
+        Ok((mut camera_transform, mut focus, projection)),
+        Ok((player_transform, velocity, contacts)),
+    ) = (camera_query.get_single_mut(), player_query.get_single())
+    else {
+        return;
+    };
+
+    let delta = time.delta_seconds();
+    let player = player_transform.translation.truncate();
+    let position = match focus.position {
+        Some(position) => update_focus(position, player, contacts.on_ground, &config),
+        None => player,
+    };
+    focus.position = Some(position);
+
+    if velocity.linvel.x.abs() > FACING_THRESHOLD {
+        focus.facing = velocity.linvel.x.signum();
+    }
+    let look_ahead = focus.facing * config.look_ahead;
+    focus.look_ahead +=
+        (look_ahead - focus.look_ahead) * smoothing_factor(config.look_ahead_rate, delta);
+
+    let view = view_size(projection);
+    let mut target = position + Vec2::new(focus.look_ahead, 0.) - view / 2.;
+    let current_bounds = level_query
+        .iter()
+        .find_map(|(level_handle, level_transform)| {
+            let level = &ldtk_levels.get(level_handle)?.level;
+            (progress.current.as_ref() == Some(&level.iid))
+                .then(|| level_bounds(level, level_transform))
+        });
+    if let Some(bounds) = current_bounds {
+        target = clamp_view(target, view, bounds);
+    }
+    focus.bounds = current_bounds;
+
+    let diff = target - camera_transform.translation.truncate();
+    camera_transform.translation += (diff * smoothing_factor(config.smoothing, delta)).extend(0.);
+}
+
+fn remove_camera_effects(mut camera_query: Query<(&mut Transform, &mut CameraEffects)>) {
+    for (mut camera_transform, mut effects) in camera_query.iter_mut() {
+        camera_transform.translation -= effects.offset.extend(0.);
+        effects.offset = Vec2::ZERO;
+    }
+}
+
+/// Smooth noise between -1 and 1, different for each seed.
+fn shake_noise(t: f32, seed: f32) -> f32 {
+    ((t + seed).sin() + 0.5 * (2.3 * t + 1.7 * seed).sin() + 0.25 * (5.1 * t + 0.3 * seed).sin())
+        / 1.75
+}
+
+/// Limits a shake along one axis so that it does not move a view past the bounds, except where
+/// the view was already past them, such as while easing over to the next level.
+fn clamp_shake(shake: f32, corner: f32, view: f32, min: f32, max: f32) -> f32 {
+    if max - min <= view {
+        // the level does not fill the view along this axis anyway
+        shake
+    } else {
+        shake.clamp((min - corner).min(0.), (max - view - corner).max(0.))
+    }
+}
+
+/// Shakes and zooms the camera around the position it followed the player to. Runs on real time,
+/// so it carries on through hitstop.
+fn apply_camera_effects(
+    time: Res<Time>,
+    config: Res<CameraConfig>,
+    mut rng: ResMut<GameRng>,
+    mut shake_events: EventReader<CameraShake>,
+    mut zoom_events: EventReader<ZoomPunch>,
+    mut camera_query: Query<
+        (
+            &mut Transform,
+            &mut OrthographicProjection,
+            &mut CameraEffects,
+            &CameraFocus,
+        ),
+        With<CameraFlag>,
+    >,
 ) {
-    if let (Ok(mut camera_transform), Ok(player_transform)) =
-        (camera_query.get_single_mut(), player_query.get_single())
+    let Ok((mut camera_transform, mut projection, mut effects, focus)) =
+        camera_query.get_single_mut()
+    else {
+        return;
+    };
+
+    let delta = time.raw_delta_seconds();
+    effects.elapsed += delta;
+    effects.trauma = (effects.trauma - effects.trauma_decay * delta).max(0.);
+    effects.zoom = (effects.zoom - effects.zoom_decay * delta).max(0.);
+    for CameraShake {
+        intensity,
+        duration,
+    } in shake_events.iter()
     {
-        let diff = player_transform.translation + Vec3::new(-WIDTH / 2., -HEIGHT / 2., 0.)
-            - camera_transform.translation;
-        camera_transform.translation += Vec3::new(diff.x * CAMERA_SPEED, diff.y * CAMERA_SPEED, 0.);
+        effects.trauma = (effects.trauma + intensity).min(1.);
+        effects.trauma_decay = effects.trauma / duration.max(f32::EPSILON);
+        effects.seed = rng.gen_range(0. ..100.);
+    }
+    for ZoomPunch { amount, duration } in zoom_events.iter() {
+        effects.zoom = effects.zoom.max(*amount);
+        effects.zoom_decay = effects.zoom / duration.max(f32::EPSILON);
+    }
+
+    // zoom around the centre of the view rather than its bottom left corner
+    let view = view_size(&projection);
+    let scale = 1. - effects.zoom;
+    projection.scale = scale;
+    let mut offset = view * (1. - scale) / 2.;
+
+    let t = effects.elapsed * config.shake_frequency;
+    let strength = config.max_shake * effects.trauma * effects.trauma;
+    let mut shake = strength
+        * Vec2::new(
+            shake_noise(t, effects.seed),
+            shake_noise(t, effects.seed + 10.),
+        );
+    if let Some(bounds) = focus.bounds {
+        let corner = camera_transform.translation.truncate() + offset;
+        let zoomed_view = view * scale;
+        shake = Vec2::new(
+            clamp_shake(shake.x, corner.x, zoomed_view.x, bounds.min.x, bounds.max.x),
+            clamp_shake(shake.y, corner.y, zoomed_view.y, bounds.min.y, bounds.max.y),
+        );
+    }
+    offset += shake;
+
+    camera_transform.translation += offset.extend(0.);
+    effects.offset = offset;
+}
+
+fn hitstop_system(
+    paused: Res<Paused>,
+    mut time: ResMut<Time>,
+    mut timer: ResMut<HitstopTimer>,
+    mut hitstop_events: EventReader<Hitstop>,
+) {
+    if timer.0 > 0. {
+        timer.0 -= time.raw_delta_seconds();
+        // the time stays paused for as long as the player has paused the game
+        if timer.0 <= 0. && !paused.0 {
+            time.unpause();
+        }
+    }
+    for Hitstop { duration } in hitstop_events.iter() {
+        timer.0 = timer.0.max(*duration);
+        time.pause();
+    }
+}
+
+/// Shakes the camera on ground pound impacts, hard landings and deaths.
+fn send_camera_effects(
+    player_query: Query<(), With<PlayerFlag>>,
+    mut state_events: EventReader<PlayerStateChanged>,
+    mut landed_events: EventReader<Landed>,
+    mut death_events: EventReader<PlayerDied>,
+    mut shake_events: EventWriter<CameraShake>,
+    mut zoom_events: EventWriter<ZoomPunch>,
+    mut hitstop_events: EventWriter<Hitstop>,
+) {
+    for PlayerStateChanged { from, to, .. } in state_events.iter() {
+        if *from == PlayerState::GroundPound
+            && matches!(
+                to,
+                PlayerState::Idle | PlayerState::Run | PlayerState::Crouch
+            )
+        {
+            shake_events.send(CameraShake {
+                intensity: 0.5,
+                duration: 0.4,
+            });
+            zoom_events.send(ZoomPunch {
+                amount: 0.05,
+                duration: 0.25,
+            });
+            hitstop_events.send(Hitstop { duration: 0.05 });
+        }
+    }
+    for Landed {
+        entity,
+        impact_velocity,
+    } in landed_events.iter()
+    {
+        if player_query.contains(*entity) && impact_velocity.y < -HARD_LANDING_SPEED {
+            shake_events.send(CameraShake {
+                intensity: 0.3,
+                duration: 0.3,
+            });
+        }
+    }
+    for _ in death_events.iter() {
+        shake_events.send(CameraShake {
+            intensity: 0.7,
+            duration: 0.5,
+        });
+        hitstop_events.send(Hitstop { duration: 0.1 });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIEW: Vec2 = Vec2::new(WIDTH, HEIGHT);
+
+    #[test]
+    fn view_size_is_the_game_size_until_rendered() {
+        let mut projection = OrthographicProjection::default();
+        assert_eq!(view_size(&projection), VIEW);
+        projection.area = Rect::new(0., 0., 640., 400.);
+        projection.scale = 2.;
+        assert_eq!(view_size(&projection), VIEW);
+    }
+
+    #[test]
+    fn clamp_view_keeps_the_view_inside_large_levels() {
+        let bounds = Rect::new(0., 0., 1000., 800.);
+        let inside = Vec2::new(300., 200.);
+        assert_eq!(clamp_view(inside, VIEW, bounds), inside);
+        assert_eq!(
+            clamp_view(Vec2::new(-50., -20.), VIEW, bounds),
+            Vec2::new(0., 0.)
+        );
+        assert_eq!(
+            clamp_view(Vec2::new(900., 700.), VIEW, bounds),
+            Vec2::new(1000. - WIDTH, 800. - HEIGHT)
+        );
+    }
+
+    #[test]
+    fn clamp_view_centres_levels_smaller_than_the_view() {
+        // narrower than the view, taller than it
+        let bounds = Rect::new(100., 0., 300., 800.);
+        assert_eq!(
+            clamp_view(Vec2::new(500., 900.), VIEW, bounds),
+            Vec2::new(200. - WIDTH / 2., 800. - HEIGHT)
+        );
+        // wider than the view, shorter than it
+        let bounds = Rect::new(0., 50., 1000., 150.);
+        assert_eq!(
+            clamp_view(Vec2::new(-10., 0.), VIEW, bounds),
+            Vec2::new(0., 100. - HEIGHT / 2.)
+        );
+    }
+
+    #[test]
+    fn clamp_view_pins_levels_the_size_of_the_view() {
+        let bounds = Rect::from_corners(Vec2::new(40., 60.), Vec2::new(40., 60.) + VIEW);
+        for corner in [Vec2::ZERO, Vec2::new(40., 60.), Vec2::new(500., 500.)] {
+            assert_eq!(clamp_view(corner, VIEW, bounds), Vec2::new(40., 60.));
+        }
+    }
+
+    #[test]
+    fn shake_does_not_show_past_the_level() {
+        let (min, max) = (0., 1000.);
+        // in the middle of the level
+        assert_eq!(clamp_shake(5., 300., WIDTH, min, max), 5.);
+        assert_eq!(clamp_shake(-5., 300., WIDTH, min, max), -5.);
+        // against either edge
+        assert_eq!(clamp_shake(-5., 2., WIDTH, min, max), -2.);
+        assert_eq!(clamp_shake(5., max - WIDTH, WIDTH, min, max), 0.);
+        // already past the edge, the shake can only move it back in
+        assert_eq!(clamp_shake(-5., -20., WIDTH, min, max), 0.);
+        assert_eq!(clamp_shake(5., -20., WIDTH, min, max), 5.);
+        // levels smaller than the view shake freely
+        assert_eq!(clamp_shake(-5., -60., WIDTH, 0., 200.), -5.);
+    }
+
+    #[test]
+    fn focus_stays_put_while_the_player_is_inside_the_dead_zone() {
+        let config = CameraConfig::default();
+        let focus = Vec2::new(100., 100.);
+        let half_zone = config.dead_zone / 2.;
+        for offset in [
+            Vec2::ZERO,
+            half_zone,
+            -half_zone,
+            Vec2::new(half_zone.x, -half_zone.y),
+        ] {
+            assert_eq!(update_focus(focus, focus + offset, true, &config), focus);
+        }
+        assert_eq!(
+            update_focus(
+                focus,
+                focus + Vec2::new(half_zone.x + 5., 0.),
+                true,
+                &config
+            ),
+            focus + Vec2::new(5., 0.)
+        );
+    }
+
+    #[test]
+    fn focus_follows_vertically_in_the_air_only_past_the_threshold() {
+        let config = CameraConfig::default();
+        let focus = Vec2::new(100., 100.);
+        let above = focus + Vec2::new(0., config.vertical_threshold - 1.);
+        assert_eq!(update_focus(focus, above, false, &config), focus);
+        let far_above = focus + Vec2::new(0., config.vertical_threshold + 10.);
+        assert_eq!(
+            update_focus(focus, far_above, false, &config),
+            focus + Vec2::new(0., 10.)
+        );
+        let far_below = focus - Vec2::new(0., config.vertical_threshold + 10.);
+        assert_eq!(
+            update_focus(focus, far_below, false, &config),
+            focus - Vec2::new(0., 10.)
+        );
+        // back on the ground the focus catches up to the dead zone
+        assert_eq!(
+            update_focus(focus, above, true, &config).y,
+            above.y - config.dead_zone.y / 2.
+        );
+    }
+
+    #[test]
+    fn negative_dead_zone_and_threshold_act_like_positive_ones() {
+        let config = CameraConfig::default();
+        let negative = CameraConfig {
+            dead_zone: -config.dead_zone,
+            vertical_threshold: -config.vertical_threshold,
+            ..config.clone()
+        };
+        let focus = Vec2::new(100., 100.);
+        for player in [focus + Vec2::new(30., 60.), focus - Vec2::new(5., 5.)] {
+            for on_ground in [true, false] {
+                assert_eq!(
+                    update_focus(focus, player, on_ground, &negative),
+                    update_focus(focus, player, on_ground, &config)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn smoothing_does_not_depend_on_the_frame_rate() {
+        let (rate, delta) = (3., 1. / 60.);
+        let remaining_after_one = 1. - smoothing_factor(rate, 2. * delta);
+        let remaining_after_two = (1. - smoothing_factor(rate, delta)).powi(2);
+        assert!((remaining_after_one - remaining_after_two).abs() < 1e-6);
+        assert_eq!(smoothing_factor(rate, 0.), 0.);
     }
 }
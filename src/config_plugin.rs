@@ -1,123 +1,163 @@
 use bevy::prelude::*;
-use bevy::window::{PresentMode, WindowResolution};
-use bevy_ecs_ldtk::prelude::*;
-use bevy_rapier2d::prelude::*;
-
-#[cfg(debug_assertions)]
-use {
-    crate::GameState,
-    bevy::diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin},
-    bevy::input::common_conditions::input_toggle_active,
-    bevy::window::PrimaryWindow,
-    bevy_debug_text_overlay::{screen_print, OverlayPlugin},
-    bevy_inspector_egui::quick::WorldInspectorPlugin,
+use bevy::reflect::TypeUuid;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    debug_plugin::DebugPlugin, display_plugin::DisplayPlugin, physics_plugin::PhysicsPlugin,
 };
 
 // world constants
 pub const ASPECT_RATIO: f32 = 10. / 16.;
 pub const WIDTH: f32 = 320.;
 pub const HEIGHT: f32 = WIDTH * ASPECT_RATIO;
-pub const CAMERA_SPEED: f32 = 0.04;
+pub const KILL_MARGIN: f32 = 80.;
+pub const DEATH_TIME: f32 = 0.6;
+pub const CRUMBLE_TIME: f32 = 0.5;
+pub const BLOCK_RESPAWN_TIME: f32 = 3.;
 
 // physics constants
 pub const PIXELS_PER_METER: f32 = 1.;
-pub const GRAVITY: f32 = 2000.;
-pub const PLAYER_SPEED: f32 = 250.;
-
-// gameplay constants
-pub const MAX_STAMINA: u32 = 1;
+pub const FIXED_TIMESTEP: f32 = 1. / 60.;
 
-#[cfg(debug_assertions)]
-#[derive(Resource, Default)]
-pub struct DebugOptions {
-    printed_info_enabled: bool,
+/// Player movement tuning, loaded from a `.movement.ron` asset and hot-reloaded on change.
+#[derive(Clone, Debug, Serialize, Deserialize, TypeUuid)]
+#[uuid = "5b0e6a2c-3f1d-4c8e-9a57-2d6f1b8e4c21"]
+#[serde(default)]
+pub struct MovementConfig {
+    pub gravity: f32,
+    pub run_speed: f32,
+    pub crouch_speed_factor: f32,
+    pub air_drag: f32,
+    pub stick_gain: f32,
+    pub wall_slide_speed: f32,
+    pub ground_jump_speed: f32,
+    pub wall_jump_speed: f32,
+    pub wall_jump_push: f32,
+    pub air_jump_speed: f32,
+    pub ground_pound_speed: f32,
+    pub dash_speed: f32,
+    pub dash_time: f32,
+    pub max_stamina: u32,
+    pub coyote_time: f32,
+    pub jump_buffer_time: f32,
+    pub jump_cut: f32,
+    pub drop_through_time: f32,
 }
 
-pub struct ConfigPlugin;
-impl Plugin for ConfigPlugin {
-    fn build(&self, app: &mut App) {
-        app.insert_resource(Msaa::Sample4)
-            .add_plugins(
-                DefaultPlugins
-                    .set(WindowPlugin {
-                        primary_window: Some(Window {
-                            title: "bevy_trunk_template".to_string(),
-                            canvas: Some("#bevy".to_owned()),
-                            fit_canvas_to_parent: true,
-                            present_mode: PresentMode::AutoVsync,
-                            resolution: WindowResolution::new(WIDTH, HEIGHT),
-                            ..default()
-                        }),
-                        ..default()
-                    })
-                    .set(AssetPlugin {
-                        watch_for_changes: true,
-                        ..Default::default()
-                    })
-                    .set(ImagePlugin::default_nearest()),
-            )
-            .add_plugin(LdtkPlugin)
-            .insert_resource(ClearColor(Color::rgb(0.1, 0.1, 0.2)))
-            .add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(
-                PIXELS_PER_METER,
-            ))
-            .insert_resource(RapierConfiguration {
-                gravity: Vec2 { x: 0., y: -GRAVITY },
-                ..Default::default()
-            });
-
-        #[cfg(debug_assertions)]
-        {
-            app.insert_resource(DebugOptions::default())
-                .add_plugin(OverlayPlugin::default())
-                .add_plugin(RapierDebugRenderPlugin::default().disabled())
-                .add_plugin(FrameTimeDiagnosticsPlugin::default())
-                .add_plugin(
-                    WorldInspectorPlugin::default()
-                        .run_if(input_toggle_active(false, KeyCode::Key3)),
-                )
-                .add_system(debug_toggle_system)
-                .add_system(debug_system);
+impl Default for MovementConfig {
+    fn default() -> Self {
+        Self {
+            gravity: 2000.,
+            run_speed: 250.,
+            crouch_speed_factor: 0.5,
+            air_drag: 0.97,
+            stick_gain: 1.3,
+            wall_slide_speed: 15.,
+            ground_jump_speed: 500.,
+            wall_jump_speed: 400.,
+            wall_jump_push: 300.,
+            air_jump_speed: 400.,
+            ground_pound_speed: 800.,
+            dash_speed: 450.,
+            dash_time: 0.15,
+            max_stamina: 1,
+            coyote_time: 0.1,
+            jump_buffer_time: 0.1,
+            jump_cut: 0.5,
+            drop_through_time: 0.2,
         }
     }
 }
 
-#[cfg(debug_assertions)]
-fn debug_system(
-    time: Res<Time>,
-    debug_options: Res<DebugOptions>,
-    window_query: Query<&Window, With<PrimaryWindow>>,
-    app_state: Res<State<GameState>>,
-    diagnostics: Res<Diagnostics>,
-) {
-    let current_time = time.elapsed_seconds();
-    let at_interval = |t: f32| current_time % t < time.delta_seconds();
-    if debug_options.printed_info_enabled && at_interval(0.25) {
-        if let Some(fps) = diagnostics.get(FrameTimeDiagnosticsPlugin::FPS) {
-            if let Some(fps) = fps.value() {
-                screen_print!(sec: 0.3, col: Color::CYAN, "fps: {fps}");
-            };
-        };
-        screen_print!(sec: 0.3, col: Color::CYAN, "game state: {:?}", app_state.0);
-        if let Ok(window) = window_query.get_single() {
-            if let Some(position) = window.cursor_position() {
-                screen_print!(sec: 0.3, col: Color::CYAN, "cursor_position: {}", position);
-            };
-        };
+/// Settings for the window, physics, debug tooling and assets, read by the plugins that
+/// [ConfigPlugin] is made of when they are built. Any of those plugins can also be added on its
+/// own, using the config inserted as a resource beforehand or the default one.
+#[derive(Resource, Clone, Debug)]
+pub struct PlatformerConfig {
+    pub title: String,
+    /// Window size in logical pixels, the camera always shows [WIDTH] world units across.
+    pub resolution: Vec2,
+    pub pixels_per_meter: f32,
+    /// Overrides the gravity of the movement config when set.
+    pub gravity: Option<f32>,
+    pub debug: bool,
+    pub asset_folder: String,
+    /// Dynamic asset manifest, relative to the asset folder.
+    pub manifest: String,
+}
+
+impl Default for PlatformerConfig {
+    fn default() -> Self {
+        Self {
+            title: "platformer".to_string(),
+            resolution: Vec2::new(WIDTH, HEIGHT),
+            pixels_per_meter: PIXELS_PER_METER,
+            gravity: None,
+            debug: cfg!(debug_assertions),
+            asset_folder: "assets".to_string(),
+            manifest: "manifest.assets.ron".to_string(),
+        }
     }
 }
 
-#[cfg(debug_assertions)]
-fn debug_toggle_system(
-    input: Res<Input<KeyCode>>,
-    mut debug_options: ResMut<DebugOptions>,
-    mut rapier_debug: ResMut<DebugRenderContext>,
-) {
-    if input.just_pressed(KeyCode::Key1) {
-        debug_options.printed_info_enabled = !debug_options.printed_info_enabled;
+impl PlatformerConfig {
+    /// The config inserted into the app, or the default one.
+    pub fn get(app: &App) -> Self {
+        app.world
+            .get_resource::<Self>()
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    pub fn with_resolution(mut self, width: f32, height: f32) -> Self {
+        self.resolution = Vec2::new(width, height);
+        self
+    }
+
+    pub fn with_pixels_per_meter(mut self, pixels_per_meter: f32) -> Self {
+        self.pixels_per_meter = pixels_per_meter;
+        self
+    }
+
+    pub fn with_gravity(mut self, gravity: f32) -> Self {
+        self.gravity = Some(gravity);
+        self
+    }
+
+    pub fn with_debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    pub fn with_asset_folder(mut self, asset_folder: impl Into<String>) -> Self {
+        self.asset_folder = asset_folder.into();
+        self
+    }
+
+    pub fn with_manifest(mut self, manifest: impl Into<String>) -> Self {
+        self.manifest = manifest.into();
+        self
     }
-    if input.just_pressed(KeyCode::Key2) {
-        rapier_debug.enabled = !rapier_debug.enabled;
+}
+
+/// Window, physics and, when enabled, debug tooling for running the game on its own.
+#[derive(Default)]
+pub struct ConfigPlugin(pub PlatformerConfig);
+
+impl Plugin for ConfigPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.0.clone())
+            .add_plugin(DisplayPlugin)
+            .add_plugin(PhysicsPlugin);
+
+        if self.0.debug {
+            app.add_plugin(DebugPlugin);
+        }
     }
 }
 
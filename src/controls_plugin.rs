@@ -1,13 +1,210 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
 use bevy::input::gamepad::GamepadEvent;
+use bevy::input::InputSystem;
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{config_plugin::PlatformerConfig, in_game, physics_plugin::FixedSet};
+
+/// Bindings file, relative to the asset folder.
+pub const BINDINGS_FILE: &str = "bindings.ron";
+
+#[derive(Resource)]
+struct BindingsPath(PathBuf);
+
+impl BindingsPath {
+    /// The bindings file in the asset folder, found the same way the asset server finds it.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn new(asset_folder: &str) -> Self {
+        BindingsPath(
+            bevy::asset::FileAssetIo::get_base_path()
+                .join(asset_folder)
+                .join(BINDINGS_FILE),
+        )
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn new(asset_folder: &str) -> Self {
+        BindingsPath(Path::new(asset_folder).join(BINDINGS_FILE))
+    }
+}
 
 #[derive(Resource)]
 pub struct CurrentGamepad(pub Gamepad);
 
+/// Set while the game is paused with [Action::Pause], which stops the game time.
+#[derive(Resource, Default, Debug)]
+pub struct Paused(pub bool);
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash, Serialize, Deserialize)]
+pub enum Action {
+    Jump,
+    Left,
+    Right,
+    Down,
+    Dash,
+    Pause,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AxisBinding {
+    pub axis: GamepadAxisType,
+    pub positive: bool,
+    pub threshold: f32,
+}
+
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InputBindings {
+    pub keys: HashMap<Action, Vec<KeyCode>>,
+    pub buttons: HashMap<Action, Vec<GamepadButtonType>>,
+    pub axes: HashMap<Action, Vec<AxisBinding>>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        Self {
+            keys: HashMap::from([
+                (Action::Jump, vec![KeyCode::Up, KeyCode::Space]),
+                (Action::Left, vec![KeyCode::Left]),
+                (Action::Right, vec![KeyCode::Right]),
+                (Action::Down, vec![KeyCode::Down]),
+                (Action::Dash, vec![KeyCode::LShift]),
+                (Action::Pause, vec![KeyCode::Escape]),
+            ]),
+            buttons: HashMap::from([
+                (Action::Jump, vec![GamepadButtonType::East]),
+                (Action::Left, vec![GamepadButtonType::DPadLeft]),
+                (Action::Right, vec![GamepadButtonType::DPadRight]),
+                (Action::Down, vec![GamepadButtonType::DPadDown]),
+                (Action::Dash, vec![GamepadButtonType::West]),
+                (Action::Pause, vec![GamepadButtonType::Start]),
+            ]),
+            axes: HashMap::from([
+                (
+                    Action::Left,
+                    vec![AxisBinding {
+                        axis: GamepadAxisType::LeftStickX,
+                        positive: false,
+                        threshold: 0.3,
+                    }],
+                ),
+                (
+                    Action::Right,
+                    vec![AxisBinding {
+                        axis: GamepadAxisType::LeftStickX,
+                        positive: true,
+                        threshold: 0.3,
+                    }],
+                ),
+            ]),
+        }
+    }
+}
+
+impl InputBindings {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: &Path) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => ron::from_str(&contents).unwrap_or_else(|error| {
+                warn!(
+                    "could not parse {}, using default bindings: {error}",
+                    path.display()
+                );
+                InputBindings::default()
+            }),
+            Err(_) => InputBindings::default(),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn load(_path: &Path) -> Self {
+        InputBindings::default()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self, path: &Path) {
+        let result = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|error| error.to_string())
+            .and_then(|contents| std::fs::write(path, contents).map_err(|error| error.to_string()));
+        if let Err(error) = result {
+            warn!("could not save {}: {error}", path.display());
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn save(&self, _path: &Path) {}
+}
+
+#[derive(Clone, Copy, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ActionData {
+    pub pressed: bool,
+    pub just_pressed: bool,
+    pub just_released: bool,
+    pub value: f32,
+}
+
+#[derive(Resource, Clone, Default, Debug, Serialize, Deserialize)]
+pub struct ActionState {
+    actions: HashMap<Action, ActionData>,
+}
+
+impl ActionState {
+    pub fn get(&self, action: Action) -> ActionData {
+        self.actions.get(&action).copied().unwrap_or_default()
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.get(action).pressed
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.get(action).just_pressed
+    }
+
+    /// Analog strength of the action, 1.0 for digital inputs.
+    pub fn value(&self, action: Action) -> f32 {
+        self.get(action).value
+    }
+
+    /// Copy of the state without the idle actions, which is all a replay needs to store. Pausing
+    /// is left out too, as replays skip the frames spent paused.
+    pub fn snapshot(&self) -> ActionState {
+        ActionState {
+            actions: self
+                .actions
+                .iter()
+                .filter(|(action, data)| {
+                    **action != Action::Pause && **data != ActionData::default()
+                })
+                .map(|(action, data)| (*action, *data))
+                .collect(),
+        }
+    }
+}
+
 pub struct ControlsPlugin;
 impl Plugin for ControlsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(controls_system);
+        let path = BindingsPath::new(&PlatformerConfig::get(app).asset_folder);
+        app.insert_resource(InputBindings::load(&path.0))
+            .insert_resource(path)
+            .init_resource::<ActionState>()
+            .init_resource::<Paused>()
+            .add_system(controls_system)
+            .add_system(toggle_pause.run_if(in_game))
+            .add_system(
+                update_action_state
+                    .in_base_set(CoreSet::PreUpdate)
+                    .after(InputSystem),
+            )
+            .add_system(
+                clear_action_edges
+                    .after(FixedSet::Logic)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(save_bindings);
     }
 }
 
@@ -31,3 +228,140 @@ fn controls_system(
         }
     }
 }
+
+fn update_action_state(
+    input: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    gamepad: Option<Res<CurrentGamepad>>,
+    bindings: Res<InputBindings>,
+    mut action_state: ResMut<ActionState>,
+) {
+    let mut current: HashMap<Action, f32> = HashMap::new();
+
+    for (action, keys) in &bindings.keys {
+        if keys.iter().any(|key| input.pressed(*key)) {
+            current.insert(*action, 1.);
+        }
+    }
+
+    if let Some(gp) = gamepad {
+        let gamepad = gp.0;
+        for (action, button_types) in &bindings.buttons {
+            if button_types.iter().any(|button_type| {
+                buttons.pressed(GamepadButton {
+                    gamepad,
+                    button_type: *button_type,
+                })
+            }) {
+                current.insert(*action, 1.);
+            }
+        }
+        for (action, axis_bindings) in &bindings.axes {
+            for binding in axis_bindings {
+                if let Some(x) = axes.get(GamepadAxis {
+                    gamepad,
+                    axis_type: binding.axis,
+                }) {
+                    let x = if binding.positive { x } else { -x };
+                    if x > binding.threshold {
+                        let value = current.entry(*action).or_default();
+                        *value = value.max(x);
+                    }
+                }
+            }
+        }
+    }
+
+    for action in [
+        Action::Jump,
+        Action::Left,
+        Action::Right,
+        Action::Down,
+        Action::Dash,
+        Action::Pause,
+    ] {
+        let data = action_state.actions.entry(action).or_default();
+        let was_pressed = data.pressed;
+        data.value = current.get(&action).copied().unwrap_or(0.);
+        data.pressed = data.value > 0.;
+        // kept until a fixed step has seen them, see clear_action_edges
+        data.just_pressed |= data.pressed && !was_pressed;
+        data.just_released |= !data.pressed && was_pressed;
+    }
+}
+
+/// Clears presses and releases once a fixed step has handled them, so that they are neither
+/// missed nor repeated when frames and fixed steps do not line up.
+fn clear_action_edges(mut action_state: ResMut<ActionState>) {
+    for data in action_state.actions.values_mut() {
+        data.just_pressed = false;
+        data.just_released = false;
+    }
+}
+
+/// Pauses or resumes the game on each press of [Action::Pause]. Presses are told apart here,
+/// since the edges in the action state are only cleared by fixed steps, which do not run while
+/// the game time is paused.
+fn toggle_pause(
+    action_state: Res<ActionState>,
+    mut was_pressed: Local<bool>,
+    mut paused: ResMut<Paused>,
+    mut time: ResMut<Time>,
+) {
+    let pressed = action_state.pressed(Action::Pause);
+    if pressed && !*was_pressed {
+        paused.0 = !paused.0;
+        if paused.0 {
+            time.pause();
+        } else {
+            time.unpause();
+        }
+    }
+    *was_pressed = pressed;
+}
+
+fn save_bindings(bindings: Res<InputBindings>, path: Res<BindingsPath>) {
+    if bindings.is_changed() && !bindings.is_added() {
+        bindings.save(&path.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A bindings file of its own for each test, since tests run in parallel.
+    fn bindings_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "platformer-{}-{name}-{BINDINGS_FILE}",
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn saved_bindings_load_back_the_same() {
+        let path = bindings_path("round-trip");
+        let mut bindings = InputBindings::default();
+        bindings.keys.insert(Action::Jump, vec![KeyCode::Z]);
+        bindings.axes.remove(&Action::Left);
+        bindings.save(&path);
+        let loaded = InputBindings::load(&path);
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(loaded, bindings);
+    }
+
+    #[test]
+    fn missing_or_unreadable_bindings_fall_back_to_the_defaults() {
+        let path = bindings_path("missing");
+        assert_eq!(InputBindings::load(&path), InputBindings::default());
+
+        let path = bindings_path("unknown-action");
+        std::fs::write(&path, "(keys: { Teleport: [T] }, buttons: {}, axes: {})")
+            .expect("bindings file should be writable");
+        let loaded = InputBindings::load(&path);
+        std::fs::remove_file(&path).ok();
+        assert_eq!(loaded, InputBindings::default());
+    }
+}
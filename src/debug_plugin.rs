@@ -0,0 +1,69 @@
+use bevy::diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin};
+use bevy::input::common_conditions::input_toggle_active;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_debug_text_overlay::{screen_print, OverlayPlugin};
+use bevy_inspector_egui::quick::WorldInspectorPlugin;
+use bevy_rapier2d::prelude::*;
+
+use crate::GameState;
+
+#[derive(Resource, Default)]
+pub struct DebugOptions {
+    printed_info_enabled: bool,
+}
+
+/// On screen info (1), collider outlines (2) and the world inspector (3), each toggled by key.
+/// Needs a window, so it is only added by [crate::ConfigPlugin].
+pub struct DebugPlugin;
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(DebugOptions::default())
+            .add_plugin(OverlayPlugin::default())
+            .add_plugin(RapierDebugRenderPlugin::default().disabled())
+            .add_plugin(FrameTimeDiagnosticsPlugin)
+            .add_plugin(
+                WorldInspectorPlugin::default().run_if(input_toggle_active(false, KeyCode::Key3)),
+            )
+            .add_system(debug_toggle_system)
+            .add_system(debug_system);
+    }
+}
+
+fn debug_system(
+    time: Res<Time>,
+    debug_options: Res<DebugOptions>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    app_state: Res<State<GameState>>,
+    diagnostics: Res<Diagnostics>,
+) {
+    let current_time = time.elapsed_seconds();
+    let at_interval = |t: f32| current_time % t < time.delta_seconds();
+    if debug_options.printed_info_enabled && at_interval(0.25) {
+        if let Some(fps) = diagnostics.get(FrameTimeDiagnosticsPlugin::FPS) {
+            if let Some(fps) = fps.value() {
+                screen_print!(sec: 0.3, col: Color::CYAN, "fps: {fps}");
+            };
+        };
+        screen_print!(sec: 0.3, col: Color::CYAN, "game state: {:?}", app_state.0);
+        if let Ok(window) = window_query.get_single() {
+            if let Some(position) = window.cursor_position() {
+                screen_print!(sec: 0.3, col: Color::CYAN, "cursor_position: {}", position);
+            };
+        };
+    }
+}
+
+fn debug_toggle_system(
+    input: Res<Input<KeyCode>>,
+    mut debug_options: ResMut<DebugOptions>,
+    mut rapier_debug: ResMut<DebugRenderContext>,
+) {
+    if input.just_pressed(KeyCode::Key1) {
+        debug_options.printed_info_enabled = !debug_options.printed_info_enabled;
+    }
+    if input.just_pressed(KeyCode::Key2) {
+        rapier_debug.enabled = !rapier_debug.enabled;
+    }
+}
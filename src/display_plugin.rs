@@ -0,0 +1,37 @@
+use bevy::prelude::*;
+use bevy::window::{PresentMode, WindowResolution};
+
+use crate::config_plugin::PlatformerConfig;
+
+/// Bevy's default plugins with the game's window, asset folder and pixel art image sampling.
+pub struct DisplayPlugin;
+
+impl Plugin for DisplayPlugin {
+    fn build(&self, app: &mut App) {
+        let config = PlatformerConfig::get(app);
+        app.insert_resource(Msaa::Sample4)
+            .add_plugins(
+                DefaultPlugins
+                    .set(WindowPlugin {
+                        primary_window: Some(Window {
+                            title: config.title,
+                            canvas: Some("#bevy".to_owned()),
+                            fit_canvas_to_parent: true,
+                            present_mode: PresentMode::AutoVsync,
+                            resolution: WindowResolution::new(
+                                config.resolution.x,
+                                config.resolution.y,
+                            ),
+                            ..default()
+                        }),
+                        ..default()
+                    })
+                    .set(AssetPlugin {
+                        asset_folder: config.asset_folder,
+                        watch_for_changes: true,
+                    })
+                    .set(ImagePlugin::default_nearest()),
+            )
+            .insert_resource(ClearColor(Color::rgb(0.1, 0.1, 0.2)));
+    }
+}
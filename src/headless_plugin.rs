@@ -0,0 +1,171 @@
+use std::time::{Duration, Instant};
+
+use bevy::input::InputPlugin;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    config_plugin::PlatformerConfig,
+    controls_plugin::{Action, InputBindings},
+    physics_plugin::PhysicsPlugin,
+    player_plugin::PlayerFlag,
+    replay_plugin::ReplayMode,
+    GamePlugin,
+};
+
+const LOAD_TIMEOUT: Duration = Duration::from_secs(30);
+/// Frames run after the player spawns, for the level's colliders and sensors to be set up.
+const SETTLE_FRAMES: usize = 3;
+
+/// Runs the game without a window or renderer, in place of [crate::ConfigPlugin]. LDtk tile layers
+/// are only drawn with the `render` feature, so this needs `--no-default-features`.
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        let config = PlatformerConfig::get(app);
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(TransformPlugin)
+            .add_plugin(HierarchyPlugin)
+            .add_plugin(InputPlugin)
+            .add_plugin(AssetPlugin {
+                asset_folder: config.asset_folder,
+                watch_for_changes: false,
+            })
+            .add_plugin(ImagePlugin::default_nearest())
+            .add_asset::<TextureAtlas>()
+            .add_plugin(PhysicsPlugin);
+    }
+}
+
+/// The game running headless, advanced one fixed step at a time while actions are held down by
+/// hand, for tests to check where the player ends up.
+pub struct Simulation {
+    pub app: App,
+    pub player: Entity,
+}
+
+impl Simulation {
+    /// Loads the game's assets and runs until the player has spawned in the first level.
+    pub fn new() -> Self {
+        Self::with_replay_mode(ReplayMode::Off)
+    }
+
+    /// Like [Simulation::new], recording the run to or playing it back from a replay file. When
+    /// playing back, the recorded inputs replace any actions held down by hand.
+    pub fn with_replay_mode(mode: ReplayMode) -> Self {
+        let mut app = App::new();
+        app.insert_resource(mode)
+            .add_plugin(HeadlessPlugin)
+            .add_plugin(GamePlugin)
+            // frames take no time, fixed steps only run when asked for
+            .insert_resource(TimeUpdateStrategy::ManualInstant(Instant::now()));
+
+        let start = Instant::now();
+        let player = loop {
+            app.update();
+            let mut player_query = app.world.query_filtered::<Entity, With<PlayerFlag>>();
+            if let Some(player) = player_query.iter(&app.world).next() {
+                break player;
+            }
+            assert!(
+                start.elapsed() < LOAD_TIMEOUT,
+                "timed out waiting for the player to spawn"
+            );
+            std::thread::sleep(Duration::from_millis(1));
+        };
+        for _ in 0..SETTLE_FRAMES {
+            app.update();
+        }
+
+        Simulation { app, player }
+    }
+
+    pub fn step(&mut self) {
+        let period = self.app.world.resource::<FixedTime>().period;
+        self.app.world.resource_mut::<FixedTime>().tick(period);
+        self.app.update();
+    }
+
+    /// Runs a frame that takes `delta` of real time, with as many fixed steps as fit in the game
+    /// time that passes. Later frames take no time again.
+    pub fn advance(&mut self, delta: Duration) {
+        let last_update = self
+            .app
+            .world
+            .resource::<Time>()
+            .last_update()
+            .unwrap_or_else(Instant::now);
+        self.app
+            .insert_resource(TimeUpdateStrategy::ManualInstant(last_update + delta));
+        self.app.update();
+    }
+
+    pub fn run(&mut self, steps: usize) {
+        for _ in 0..steps {
+            self.step();
+        }
+    }
+
+    /// Presses the first key bound to the action, it stays held until released.
+    pub fn press(&mut self, action: Action) {
+        let key = self
+            .app
+            .world
+            .resource::<InputBindings>()
+            .keys
+            .get(&action)
+            .and_then(|keys| keys.first())
+            .copied()
+            .unwrap_or_else(|| panic!("no key is bound to {action:?}"));
+        self.app.world.resource_mut::<Input<KeyCode>>().press(key);
+    }
+
+    pub fn release(&mut self, action: Action) {
+        let keys = self
+            .app
+            .world
+            .resource::<InputBindings>()
+            .keys
+            .get(&action)
+            .cloned()
+            .unwrap_or_default();
+        let mut input = self.app.world.resource_mut::<Input<KeyCode>>();
+        for key in keys {
+            input.release(key);
+        }
+    }
+
+    /// Holds the action down for a number of steps, then releases it.
+    pub fn hold(&mut self, action: Action, steps: usize) {
+        self.press(action);
+        self.run(steps);
+        self.release(action);
+    }
+
+    pub fn player<T: Component>(&self) -> &T {
+        self.app
+            .world
+            .get::<T>(self.player)
+            .expect("player should have the component")
+    }
+
+    /// Moves the player to a position and stops it.
+    pub fn teleport(&mut self, position: Vec2) {
+        let mut player = self.app.world.entity_mut(self.player);
+        let mut transform = player
+            .get_mut::<Transform>()
+            .expect("player has a transform");
+        transform.translation = position.extend(transform.translation.z);
+        if let Some(mut velocity) = player.get_mut::<Velocity>() {
+            velocity.linvel = Vec2::ZERO;
+        }
+    }
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,12 +1,90 @@
+use std::collections::HashSet;
+
 use bevy::prelude::*;
+use bevy_ecs_ldtk::ldtk::{FieldInstanceEntityReference, Level};
 use bevy_ecs_ldtk::prelude::*;
+use bevy_ecs_ldtk::utils::{
+    ldtk_pixel_coords_to_translation, ldtk_pixel_coords_to_translation_pivoted,
+};
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    config_plugin::KILL_MARGIN,
+    loading_plugin::AllAssets,
+    player_plugin::{LastSafeSpot, PlayerFlag, RespawnPoint},
+    GameState,
+};
+
+const TRIGGER_SIZE: Vec2 = Vec2::new(16., 16.);
+
+/// Iids of the level the player is in and of every level they have entered so far.
+#[derive(Resource, Default, Debug)]
+pub struct LevelProgress {
+    pub current: Option<String>,
+    pub visited: HashSet<String>,
+}
+
+#[derive(Component, Clone, Debug, Default)]
+pub struct LevelExit {
+    pub entry: Option<FieldInstanceEntityReference>,
+}
 
-use crate::{loading_plugin::AllAssets, GameState};
+impl From<&EntityInstance> for LevelExit {
+    fn from(entity_instance: &EntityInstance) -> LevelExit {
+        LevelExit {
+            entry: entity_instance
+                .get_maybe_entity_ref_field("Entry")
+                .ok()
+                .cloned()
+                .flatten(),
+        }
+    }
+}
+
+#[derive(Clone, Default, Bundle, LdtkEntity)]
+pub struct LevelExitBundle {
+    #[from_entity_instance]
+    level_exit: LevelExit,
+
+    #[with(trigger_collider)]
+    collider: Collider,
+
+    sensor: Sensor,
+}
+
+#[derive(Component, Clone, Debug, Default)]
+pub struct Checkpoint {
+    pub active: bool,
+}
+
+#[derive(Clone, Default, Bundle, LdtkEntity)]
+pub struct CheckpointBundle {
+    checkpoint: Checkpoint,
+
+    #[with(trigger_collider)]
+    collider: Collider,
+
+    sensor: Sensor,
+}
+
+fn trigger_collider(_: &EntityInstance) -> Collider {
+    definition_collider(TRIGGER_SIZE)
+}
+
+/// Collider sized to an LDtk entity definition. Resized instances are scaled by their transform,
+/// so sizing to the instance as well would scale them twice.
+pub fn definition_collider(definition_size: Vec2) -> Collider {
+    Collider::cuboid(definition_size.x / 2., definition_size.y / 2.)
+}
 
 pub struct LevelPlugin;
 
 impl Plugin for LevelPlugin {
     fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<LdtkPlugin>() {
+            app.add_plugin(LdtkPlugin);
+        }
+
         app.insert_resource(LevelSelection::Index(0))
             .insert_resource(LdtkSettings {
                 set_clear_color: SetClearColor::No,
@@ -16,7 +94,20 @@ impl Plugin for LevelPlugin {
                 },
                 ..Default::default()
             })
-            .add_system(level_setup.in_schedule(OnEnter(GameState::Playing)));
+            .init_resource::<LevelProgress>()
+            .register_ldtk_entity::<LevelExitBundle>("LevelExit")
+            .register_ldtk_entity::<CheckpointBundle>("Checkpoint")
+            .add_system(level_setup.in_schedule(OnExit(GameState::Loading)))
+            .add_systems(
+                (
+                    activate_checkpoints,
+                    use_level_exits,
+                    follow_player_between_levels,
+                    track_level_progress,
+                )
+                    .chain()
+                    .in_set(OnUpdate(GameState::Playing)),
+            );
     }
 }
 
@@ -29,3 +120,160 @@ fn level_setup(mut commands: Commands, levels: Res<AllAssets>) {
         ..Default::default()
     });
 }
+
+pub fn level_bounds(level: &Level, level_transform: &GlobalTransform) -> Rect {
+    let min = level_transform.translation().truncate();
+    Rect {
+        min,
+        max: min + Vec2::new(level.px_wid as f32, level.px_hei as f32),
+    }
+}
+
+/// Level bounds grown by the level's `KillMargin` field, or by [KILL_MARGIN] when it is unset.
+/// Leaving these on any side counts as falling out of the level.
+pub fn level_kill_bounds(level: &Level, level_transform: &GlobalTransform) -> Rect {
+    let margin = level
+        .get_maybe_int_field("KillMargin")
+        .ok()
+        .copied()
+        .flatten()
+        .map_or(KILL_MARGIN, |margin| margin as f32);
+    let bounds = level_bounds(level, level_transform);
+    Rect {
+        min: bounds.min - margin,
+        max: bounds.max + margin,
+    }
+}
+
+/// World-space centre of the entity an LDtk entity reference points at, whether or not its
+/// level is currently spawned.
+pub fn entity_world_position(
+    ldtk_asset: &LdtkAsset,
+    reference: &FieldInstanceEntityReference,
+) -> Option<Vec2> {
+    let level = ldtk_asset.get_level(&LevelSelection::Iid(reference.level_iid.clone()))?;
+    let entity_instance = level
+        .layer_instances
+        .as_ref()?
+        .iter()
+        .flat_map(|layer| &layer.entity_instances)
+        .find(|entity_instance| entity_instance.iid == reference.entity_iid)?;
+
+    let level_origin = ldtk_pixel_coords_to_translation(
+        IVec2::new(level.world_x, level.world_y + level.px_hei),
+        ldtk_asset.world_height(),
+    );
+
+    Some(
+        level_origin
+            + ldtk_pixel_coords_to_translation_pivoted(
+                entity_instance.px,
+                level.px_hei,
+                IVec2::new(entity_instance.width, entity_instance.height),
+                entity_instance.pivot,
+            ),
+    )
+}
+
+fn activate_checkpoints(
+    rapier_context: Res<RapierContext>,
+    mut checkpoint_query: Query<(Entity, &mut Checkpoint, &GlobalTransform)>,
+    mut player_query: Query<(Entity, &mut RespawnPoint), With<PlayerFlag>>,
+) {
+    for (player, mut respawn_point) in &mut player_query {
+        let touched = checkpoint_query
+            .iter()
+            .find(|(entity, checkpoint, _)| {
+                !checkpoint.active
+                    && rapier_context.intersection_pair(*entity, player) == Some(true)
+            })
+            .map(|(entity, _, transform)| (entity, transform.translation()));
+
+        if let Some((touched, position)) = touched {
+            for (entity, mut checkpoint, _) in &mut checkpoint_query {
+                checkpoint.active = entity == touched;
+            }
+            **respawn_point = Some(position);
+        }
+    }
+}
+
+fn use_level_exits(
+    rapier_context: Res<RapierContext>,
+    assets: Res<AllAssets>,
+    ldtk_assets: Res<Assets<LdtkAsset>>,
+    mut level_selection: ResMut<LevelSelection>,
+    exit_query: Query<(Entity, &LevelExit)>,
+    mut player_query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Velocity,
+            &mut LastSafeSpot,
+            &mut RespawnPoint,
+        ),
+        With<PlayerFlag>,
+    >,
+) {
+    let Some(ldtk_asset) = ldtk_assets.get(&assets.level0) else {
+        return;
+    };
+
+    for (player, mut transform, mut velocity, mut last_safe_spot, mut respawn_point) in
+        &mut player_query
+    {
+        for (exit_entity, exit) in &exit_query {
+            if rapier_context.intersection_pair(exit_entity, player) != Some(true) {
+                continue;
+            }
+            if let Some(entry) = &exit.entry {
+                if let Some(position) = entity_world_position(ldtk_asset, entry) {
+                    transform.translation = position.extend(transform.translation.z);
+                    velocity.linvel = Vec2::ZERO;
+                    **last_safe_spot = transform.translation;
+                    **respawn_point = Some(transform.translation);
+                    *level_selection = LevelSelection::Iid(entry.level_iid.clone());
+                }
+            }
+        }
+    }
+}
+
+fn follow_player_between_levels(
+    progress: Res<LevelProgress>,
+    ldtk_levels: Res<Assets<LdtkLevel>>,
+    mut level_selection: ResMut<LevelSelection>,
+    level_query: Query<(&Handle<LdtkLevel>, &GlobalTransform)>,
+    player_query: Query<&GlobalTransform, With<PlayerFlag>>,
+) {
+    for player_transform in &player_query {
+        let player_position = player_transform.translation().truncate();
+        for (level_handle, level_transform) in &level_query {
+            if let Some(ldtk_level) = ldtk_levels.get(level_handle) {
+                let inside =
+                    level_bounds(&ldtk_level.level, level_transform).contains(player_position);
+                if inside && progress.current.as_ref() != Some(&ldtk_level.level.iid) {
+                    *level_selection = LevelSelection::Iid(ldtk_level.level.iid.clone());
+                }
+            }
+        }
+    }
+}
+
+fn track_level_progress(
+    assets: Res<AllAssets>,
+    ldtk_assets: Res<Assets<LdtkAsset>>,
+    level_selection: Res<LevelSelection>,
+    mut progress: ResMut<LevelProgress>,
+) {
+    if !level_selection.is_changed() {
+        return;
+    }
+    if let Some(level) = ldtk_assets
+        .get(&assets.level0)
+        .and_then(|ldtk_asset| ldtk_asset.get_level(&level_selection))
+    {
+        progress.current = Some(level.iid.clone());
+        progress.visited.insert(level.iid.clone());
+    }
+}
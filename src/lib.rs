@@ -2,38 +2,87 @@
 
 use bevy::prelude::*;
 
+mod animation_plugin;
+mod audio_plugin;
+mod breakable_plugin;
 mod camera_plugin;
 mod config_plugin;
 mod controls_plugin;
+mod debug_plugin;
+mod display_plugin;
+#[cfg(feature = "test-harness")]
+mod headless_plugin;
 mod level_plugin;
 mod loading_plugin;
+mod physics_plugin;
+mod platform_plugin;
 mod player_plugin;
+mod replay_plugin;
 mod wall_plugin;
 
+use animation_plugin::AnimationPlugin;
+use audio_plugin::AudioPlugin;
+use breakable_plugin::BreakablePlugin;
 use camera_plugin::CameraPlugin;
-pub use config_plugin::{get_world_position, ConfigPlugin, ASPECT_RATIO, HEIGHT, WIDTH};
+pub use camera_plugin::{CameraConfig, CameraEffects, CameraShake, Hitstop, ZoomPunch};
+pub use config_plugin::{
+    get_world_position, ConfigPlugin, MovementConfig, PlatformerConfig, ASPECT_RATIO, HEIGHT, WIDTH,
+};
 use controls_plugin::ControlsPlugin;
+pub use controls_plugin::{Action, AxisBinding, InputBindings, Paused};
+pub use debug_plugin::DebugPlugin;
+pub use display_plugin::DisplayPlugin;
+#[cfg(feature = "test-harness")]
+pub use headless_plugin::{HeadlessPlugin, Simulation};
 use level_plugin::LevelPlugin;
+pub use level_plugin::LevelProgress;
 pub use loading_plugin::{AllAssets, LoadingPlugin};
+pub use physics_plugin::PhysicsPlugin;
+pub use platform_plugin::MovingPlatform;
+use platform_plugin::PlatformPlugin;
 use player_plugin::PlayerPlugin;
+pub use player_plugin::{
+    CarriedVelocity, LastSafeSpot, MovePerformed, PlayerDied, PlayerRespawned, PlayerState,
+    PlayerStateChanged, RespawnMode, Stamina,
+};
+use replay_plugin::ReplayPlugin;
+pub use replay_plugin::{GameRng, Replay, ReplayMode};
 use wall_plugin::WallPlugin;
+pub use wall_plugin::{
+    BecameStable, ContactDetection, ContactSensorConfig, ContactSide, Landed, LeftGround,
+    SensorShape, Surface, TouchedWall,
+};
 
 #[derive(Clone, Eq, PartialEq, Debug, Hash, Default, States)]
 enum GameState {
     #[default]
     Loading,
     Playing,
+    /// The player has died and is waiting to respawn, movement input is ignored.
+    Dying,
+}
+
+/// Run condition for systems that keep running while the player is dying, such as contact
+/// tracking and animation.
+fn in_game(state: Res<State<GameState>>) -> bool {
+    state.0 != GameState::Loading
 }
 
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugin(LoadingPlugin)
+        app.init_resource::<PlatformerConfig>()
+            .add_plugin(LoadingPlugin)
             .add_plugin(CameraPlugin)
             .add_plugin(ControlsPlugin)
+            .add_plugin(ReplayPlugin)
             .add_plugin(LevelPlugin)
             .add_plugin(WallPlugin)
-            .add_plugin(PlayerPlugin);
+            .add_plugin(BreakablePlugin)
+            .add_plugin(PlatformPlugin)
+            .add_plugin(PlayerPlugin)
+            .add_plugin(AnimationPlugin)
+            .add_plugin(AudioPlugin);
     }
 }
@@ -1,27 +1,98 @@
+use std::marker::PhantomData;
+
+use bevy::asset::{Asset, AssetLoader, LoadContext, LoadedAsset};
 use bevy::prelude::*;
+use bevy::utils::{BoxedFuture, HashMap};
 use bevy_asset_loader::prelude::*;
 use bevy_ecs_ldtk::LdtkAsset;
+use bevy_kira_audio::AudioSource;
+use serde::de::DeserializeOwned;
 
-use crate::GameState;
+use crate::{
+    animation_plugin::AnimationLibrary,
+    config_plugin::{MovementConfig, PlatformerConfig},
+    wall_plugin::SurfaceTable,
+    GameState,
+};
 
 #[derive(AssetCollection, Resource)]
 pub struct AllAssets {
     #[asset(key = "textures.kitty")]
     pub kitty: Handle<Image>,
+    #[asset(key = "textures.kitty_sheet")]
+    pub kitty_sheet: Handle<TextureAtlas>,
     #[asset(key = "ldtk.level0")]
     pub level0: Handle<LdtkAsset>,
+    #[asset(key = "config.movement")]
+    pub movement: Handle<MovementConfig>,
+    #[asset(key = "config.surfaces")]
+    pub surfaces: Handle<SurfaceTable>,
+    #[asset(key = "animations.kitty")]
+    pub kitty_animations: Handle<AnimationLibrary>,
+    #[asset(key = "audio.jump")]
+    pub jump_sound: Handle<AudioSource>,
+    #[asset(key = "audio.wall_jump")]
+    pub wall_jump_sound: Handle<AudioSource>,
+    #[asset(key = "audio.air_jump")]
+    pub air_jump_sound: Handle<AudioSource>,
+    #[asset(key = "audio.ground_pound")]
+    pub ground_pound_sound: Handle<AudioSource>,
+    #[asset(key = "audio.respawn")]
+    pub respawn_sound: Handle<AudioSource>,
+    #[asset(key = "audio.music", collection(typed, mapped))]
+    pub music: HashMap<String, Handle<AudioSource>>,
+}
+
+/// Loads any deserializable asset from a RON file with the given extensions.
+pub struct RonAssetLoader<A> {
+    extensions: &'static [&'static str],
+    _asset: PhantomData<A>,
+}
+
+impl<A> RonAssetLoader<A> {
+    pub fn new(extensions: &'static [&'static str]) -> Self {
+        Self {
+            extensions,
+            _asset: PhantomData,
+        }
+    }
+}
+
+impl<A: Asset + DeserializeOwned> AssetLoader for RonAssetLoader<A> {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let asset = ron::de::from_bytes::<A>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(asset));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        self.extensions
+    }
 }
 
 pub struct LoadingPlugin;
 impl Plugin for LoadingPlugin {
     fn build(&self, app: &mut App) {
+        let config = PlatformerConfig::get(app);
         app.add_state::<GameState>()
+            .add_asset::<MovementConfig>()
+            .add_asset_loader(RonAssetLoader::<MovementConfig>::new(&["movement.ron"]))
+            .add_asset::<SurfaceTable>()
+            .add_asset_loader(RonAssetLoader::<SurfaceTable>::new(&["surfaces.ron"]))
+            .add_asset::<AnimationLibrary>()
+            .add_asset_loader(RonAssetLoader::<AnimationLibrary>::new(&["anim.ron"]))
             .add_loading_state(
                 LoadingState::new(GameState::Loading).continue_to_state(GameState::Playing),
             )
             .add_dynamic_collection_to_loading_state::<_, StandardDynamicAssetCollection>(
                 GameState::Loading,
-                "manifest.assets.ron",
+                &config.manifest,
             )
             .add_collection_to_loading_state::<_, AllAssets>(GameState::Loading);
     }
@@ -5,10 +5,38 @@ use bevy::prelude::App;
 
 use platformer::ConfigPlugin;
 use platformer::GamePlugin;
+use platformer::ReplayMode;
+
+/// Records to, or plays back from, the path after `--record` or `--replay`.
+#[cfg(not(target_arch = "wasm32"))]
+fn replay_mode_from_args() -> ReplayMode {
+    use std::path::PathBuf;
+
+    let args: Vec<String> = std::env::args().collect();
+    let path_after = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .and_then(|index| args.get(index + 1))
+            .map(PathBuf::from)
+    };
+    if let Some(path) = path_after("--replay") {
+        ReplayMode::Replay(path)
+    } else if let Some(path) = path_after("--record") {
+        ReplayMode::Record(path)
+    } else {
+        ReplayMode::Off
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn replay_mode_from_args() -> ReplayMode {
+    ReplayMode::Off
+}
 
 fn main() {
     App::new()
-        .add_plugin(ConfigPlugin)
+        .insert_resource(replay_mode_from_args())
+        .add_plugin(ConfigPlugin::default())
         .add_plugin(GamePlugin)
         .run();
 }
@@ -0,0 +1,134 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    config_plugin::{MovementConfig, PlatformerConfig, FIXED_TIMESTEP},
+    wall_plugin::OneWayPlatformHooks,
+};
+
+type Physics = RapierPhysicsPlugin<OneWayPlatformHooks<'static, 'static>>;
+
+/// Stages of every fixed step, in order. Rapier steps first, then contacts are read back and
+/// movement sets the velocities used by the next step.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
+pub enum FixedSet {
+    SyncBackend,
+    SyncBackendFlush,
+    StepSimulation,
+    Writeback,
+    Contacts,
+    Logic,
+}
+
+/// Blends the rendered translation of a physics body between its last two fixed steps.
+/// Translations set outside of the physics step, such as teleports, are taken as is.
+#[derive(Component, Clone, Default, Debug)]
+pub struct InterpolatedTransform {
+    previous: Vec3,
+    current: Vec3,
+    rendered: Option<Vec3>,
+}
+
+impl InterpolatedTransform {
+    fn snap(&mut self, translation: Vec3) {
+        self.previous = translation;
+        self.current = translation;
+    }
+}
+
+pub struct PhysicsPlugin;
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        let config = PlatformerConfig::get(app);
+        app.insert_resource(FixedTime::new_from_secs(FIXED_TIMESTEP))
+            .add_plugin(
+                Physics::pixels_per_meter(config.pixels_per_meter).with_default_system_setup(false),
+            )
+            .insert_resource(RapierConfiguration {
+                gravity: Vec2 {
+                    x: 0.,
+                    y: -config.gravity.unwrap_or(MovementConfig::default().gravity),
+                },
+                timestep_mode: TimestepMode::Fixed {
+                    dt: FIXED_TIMESTEP,
+                    substeps: 1,
+                },
+                ..Default::default()
+            })
+            .edit_schedule(CoreSchedule::FixedUpdate, |schedule| {
+                schedule
+                    .configure_sets(
+                        (
+                            FixedSet::SyncBackend,
+                            FixedSet::SyncBackendFlush,
+                            FixedSet::StepSimulation,
+                            FixedSet::Writeback,
+                            FixedSet::Contacts,
+                            FixedSet::Logic,
+                        )
+                            .chain(),
+                    )
+                    .add_systems(
+                        Physics::get_systems(PhysicsSet::SyncBackend).in_set(FixedSet::SyncBackend),
+                    )
+                    .add_systems(
+                        Physics::get_systems(PhysicsSet::SyncBackendFlush)
+                            .in_set(FixedSet::SyncBackendFlush),
+                    )
+                    .add_systems(
+                        Physics::get_systems(PhysicsSet::StepSimulation)
+                            .in_set(FixedSet::StepSimulation),
+                    )
+                    .add_systems(
+                        Physics::get_systems(PhysicsSet::Writeback).in_set(FixedSet::Writeback),
+                    )
+                    .add_system(restore_physics_transforms.before(FixedSet::SyncBackend))
+                    .add_system(
+                        record_physics_transforms
+                            .after(FixedSet::Writeback)
+                            .before(FixedSet::Contacts),
+                    );
+            })
+            .add_system(interpolate_transforms);
+    }
+}
+
+fn restore_physics_transforms(mut query: Query<(&mut Transform, &mut InterpolatedTransform)>) {
+    for (mut transform, mut interpolated) in &mut query {
+        match interpolated.rendered.take() {
+            Some(rendered) if rendered == transform.translation => {
+                transform.translation = interpolated.current;
+            }
+            _ if transform.translation != interpolated.current => {
+                interpolated.snap(transform.translation);
+            }
+            _ => (),
+        }
+    }
+}
+
+fn record_physics_transforms(mut query: Query<(&Transform, &mut InterpolatedTransform)>) {
+    for (transform, mut interpolated) in &mut query {
+        interpolated.previous = interpolated.current;
+        interpolated.current = transform.translation;
+    }
+}
+
+/// Moves bodies to where they are between the last two steps, by how far the fixed clock is
+/// into the next step.
+pub fn interpolate_transforms(
+    fixed_time: Res<FixedTime>,
+    mut query: Query<(&mut Transform, &mut InterpolatedTransform)>,
+) {
+    let alpha = (fixed_time.accumulated().as_secs_f32() / fixed_time.period.as_secs_f32()).min(1.);
+    for (mut transform, mut interpolated) in &mut query {
+        let expected = interpolated.rendered.unwrap_or(interpolated.current);
+        if transform.translation != expected {
+            interpolated.snap(transform.translation);
+        }
+        let rendered = interpolated.previous.lerp(interpolated.current, alpha);
+        transform.translation = rendered;
+        interpolated.rendered = Some(rendered);
+    }
+}
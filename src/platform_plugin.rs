@@ -0,0 +1,208 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    level_plugin::definition_collider,
+    physics_plugin::{FixedSet, InterpolatedTransform},
+    wall_plugin::LevelGrids,
+    GameState,
+};
+
+const PLATFORM_SIZE: Vec2 = Vec2::new(32., 8.);
+const PLATFORM_COLOR: Color = Color::rgb(0.55, 0.61, 0.71);
+const DEFAULT_SPEED: f32 = 40.;
+const DEFAULT_WAIT_TIME: f32 = 0.5;
+/// Distance below which a platform counts as having reached its target point.
+const ARRIVAL_DISTANCE: f32 = 0.1;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum PathMode {
+    /// Goes from the last point straight back to the first one.
+    #[default]
+    Loop,
+    /// Travels the path backwards after reaching either end.
+    PingPong,
+}
+
+#[derive(Component, Clone, Debug, Default)]
+pub struct MovingPlatform {
+    /// `Path` field of the entity in LDtk grid coordinates, resolved into `path` once spawned.
+    pub points: Vec<IVec2>,
+    /// Translations visited in order, starting where the platform was placed.
+    pub path: Vec<Vec2>,
+    pub speed: f32,
+    pub wait_time: f32,
+    pub mode: PathMode,
+    pub target: usize,
+    pub reversing: bool,
+    pub waiting: f32,
+}
+
+impl MovingPlatform {
+    fn advance(&mut self) {
+        let last = self.path.len() - 1;
+        match self.mode {
+            PathMode::Loop => self.target = (self.target + 1) % self.path.len(),
+            PathMode::PingPong => {
+                if self.target == last {
+                    self.reversing = true;
+                } else if self.target == 0 {
+                    self.reversing = false;
+                }
+                self.target = if self.reversing {
+                    self.target - 1
+                } else {
+                    self.target + 1
+                };
+            }
+        }
+    }
+}
+
+impl From<&EntityInstance> for MovingPlatform {
+    fn from(entity_instance: &EntityInstance) -> MovingPlatform {
+        let mode = match entity_instance
+            .get_maybe_enum_field("Mode")
+            .ok()
+            .cloned()
+            .flatten()
+            .as_deref()
+        {
+            Some("PingPong") => PathMode::PingPong,
+            _ => PathMode::Loop,
+        };
+        MovingPlatform {
+            points: entity_instance
+                .iter_points_field("Path")
+                .map(|points| points.copied().collect())
+                .unwrap_or_default(),
+            speed: entity_instance
+                .get_maybe_float_field("Speed")
+                .ok()
+                .copied()
+                .flatten()
+                .unwrap_or(DEFAULT_SPEED),
+            wait_time: entity_instance
+                .get_maybe_float_field("WaitTime")
+                .ok()
+                .copied()
+                .flatten()
+                .unwrap_or(DEFAULT_WAIT_TIME),
+            mode,
+            ..default()
+        }
+    }
+}
+
+#[derive(Clone, Default, Bundle, LdtkEntity)]
+pub struct MovingPlatformBundle {
+    #[from_entity_instance]
+    moving_platform: MovingPlatform,
+
+    #[from_entity_instance]
+    entity_instance: EntityInstance,
+
+    #[with(platform_collider)]
+    collider: Collider,
+
+    #[with(platform_rigid_body)]
+    rigid_body: RigidBody,
+
+    velocity: Velocity,
+    interpolated_transform: InterpolatedTransform,
+
+    #[with(platform_sprite)]
+    #[bundle]
+    sprite_bundle: SpriteBundle,
+}
+
+fn platform_collider(_: &EntityInstance) -> Collider {
+    definition_collider(PLATFORM_SIZE)
+}
+
+fn platform_rigid_body(_: &EntityInstance) -> RigidBody {
+    RigidBody::KinematicVelocityBased
+}
+
+fn platform_sprite(_: &EntityInstance) -> SpriteBundle {
+    SpriteBundle {
+        sprite: Sprite {
+            color: PLATFORM_COLOR,
+            custom_size: Some(PLATFORM_SIZE),
+            ..default()
+        },
+        ..default()
+    }
+}
+
+pub struct PlatformPlugin;
+impl Plugin for PlatformPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<MovingPlatformBundle>("MovingPlatform")
+            .add_system(resolve_platform_paths.in_set(OnUpdate(GameState::Playing)))
+            .add_system(
+                move_platforms
+                    .run_if(in_state(GameState::Playing))
+                    .in_set(FixedSet::Logic)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            );
+    }
+}
+
+/// Converts the LDtk path points into translations, each point being where the centre of the
+/// platform passes.
+fn resolve_platform_paths(
+    mut platform_query: Query<
+        (&mut MovingPlatform, &EntityInstance, &Transform, &Parent),
+        Added<MovingPlatform>,
+    >,
+    level_grids: LevelGrids,
+) {
+    // LDtk entities are spawned as children of their level rather than of their layer
+    for (mut platform, entity_instance, transform, parent) in &mut platform_query {
+        let Some((_, _, grid_size)) = level_grids.grid(parent.get()) else {
+            continue;
+        };
+        let size = Vec2::new(entity_instance.width as f32, entity_instance.height as f32);
+        let centre = entity_instance.px.as_vec2() - entity_instance.pivot * size + size / 2.;
+        let start = transform.translation.truncate();
+
+        let mut path = vec![start];
+        path.extend(platform.points.iter().map(|point| {
+            let offset = (point.as_vec2() + 0.5) * grid_size as f32 - centre;
+            start + Vec2::new(offset.x, -offset.y)
+        }));
+        platform.path = path;
+        platform.target = 1.min(platform.path.len() - 1);
+    }
+}
+
+fn move_platforms(
+    fixed_time: Res<FixedTime>,
+    mut platform_query: Query<(&mut MovingPlatform, &Transform, &mut Velocity)>,
+) {
+    let delta = fixed_time.period.as_secs_f32();
+    for (mut platform, transform, mut velocity) in &mut platform_query {
+        if platform.path.len() < 2 || delta <= 0. {
+            velocity.linvel = Vec2::ZERO;
+            continue;
+        }
+        if platform.waiting > 0. {
+            platform.waiting -= delta;
+            velocity.linvel = Vec2::ZERO;
+            continue;
+        }
+
+        let offset = platform.path[platform.target] - transform.translation.truncate();
+        let distance = offset.length();
+        if distance < ARRIVAL_DISTANCE {
+            platform.advance();
+            platform.waiting = platform.wait_time;
+            velocity.linvel = Vec2::ZERO;
+        } else {
+            // slow down on the last step to land exactly on the point
+            velocity.linvel = offset / distance * platform.speed.min(distance / delta);
+        }
+    }
+}
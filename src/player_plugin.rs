@@ -1,14 +1,26 @@
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
 use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    config_plugin::{MAX_STAMINA, PLAYER_SPEED},
-    controls_plugin::CurrentGamepad,
-    wall_plugin::ContactDetection,
+    config_plugin::{MovementConfig, PlatformerConfig, DEATH_TIME},
+    controls_plugin::{Action, ActionState},
+    in_game,
+    level_plugin::{level_kill_bounds, LevelProgress},
+    loading_plugin::AllAssets,
+    physics_plugin::{FixedSet, InterpolatedTransform},
+    wall_plugin::{
+        collider_extents, ContactDetection, DropThrough, HazardCollider, Surface, SurfaceTable,
+        RISING_SPEED,
+    },
     GameState,
 };
 
+/// How far above the top of a ramp the feet are lifted when reaching it.
+const CREST_CLEARANCE: f32 = 1.;
+
 #[derive(Clone, Debug, Default, Bundle, LdtkIntCell)]
 pub struct ColliderBundle {
     pub collider: Collider,
@@ -55,15 +67,104 @@ impl From<&EntityInstance> for ControllerBundle {
 #[derive(Component, Clone, Default, Debug, Deref, DerefMut)]
 pub struct LastSafeSpot(Vec3);
 
-#[derive(Component, Clone, Deref, DerefMut)]
+/// Position of the last checkpoint the player touched.
+#[derive(Component, Clone, Default, Debug, Deref, DerefMut)]
+pub struct RespawnPoint(Option<Vec3>);
+
+/// Where the player goes back to after dying. `Checkpoint` falls back to
+/// the last safe spot until a checkpoint has been touched.
+#[derive(Resource, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum RespawnMode {
+    #[default]
+    Checkpoint,
+    LastSafeSpot,
+}
+
+/// Air jumps left before touching the ground again, filled up to the movement config's
+/// `max_stamina` when the player spawns, respawns or lands.
+#[derive(Component, Clone, Default, Deref, DerefMut)]
 pub struct Stamina(u32);
 
-impl Default for Stamina {
-    fn default() -> Self {
-        Self(MAX_STAMINA)
-    }
+/// Coyote time and jump buffering. `ground`, `left` and `right` are the seconds left in which
+/// a jump off the ground or either wall is still allowed after leaving it, refilled to the
+/// coyote time while touching it. `buffer` is the seconds left in which an early jump press is
+/// still honoured once a jump becomes possible.
+#[derive(Component, Clone, Default, Debug)]
+pub struct JumpGrace {
+    pub ground: f32,
+    pub left: f32,
+    pub right: f32,
+    pub buffer: f32,
+    /// Set while rising from a jump that can still be cut short by releasing jump.
+    pub rising: bool,
+    /// Set while rising from a performed jump, including air jumps and wall jumps. Suppresses
+    /// ground grace until the player is falling, since the ground sensor still overlaps the
+    /// floor for a few steps after jumping off it.
+    pub ascending: bool,
+}
+
+/// Velocity of the ground added to the player's own velocity last frame, taken back out before
+/// movement is applied so it does not build up while riding a moving platform.
+#[derive(Component, Clone, Default, Debug, Deref, DerefMut)]
+pub struct CarriedVelocity(Vec2);
+
+/// Whether the last step followed a ramp, so the climb can be dropped once past its crest.
+#[derive(Component, Clone, Default, Debug, Deref, DerefMut)]
+pub struct FollowingRamp(bool);
+
+/// Seconds left in the current dash, and whether a dash is available before landing again.
+#[derive(Component, Clone, Default, Debug)]
+pub struct DashState {
+    pub remaining: f32,
+    pub available: bool,
+    pub direction: f32,
+}
+
+/// What the player is currently doing. Jump, WallJump, AirJump, GroundPound and Dash are
+/// entered when the move is performed, the rest are derived from contacts and velocity.
+#[derive(Component, Clone, Copy, Default, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PlayerState {
+    #[default]
+    Idle,
+    Run,
+    Crouch,
+    Jump,
+    Fall,
+    WallSlide,
+    WallJump,
+    AirJump,
+    GroundPound,
+    Dash,
+    Dead,
+}
+
+pub struct PlayerStateChanged {
+    pub entity: Entity,
+    pub from: PlayerState,
+    pub to: PlayerState,
+}
+
+/// Sent when the player performs a move, Jump, WallJump, AirJump, GroundPound or Dash, as
+/// opposed to entering a state because of contacts and velocity.
+pub struct MovePerformed {
+    pub entity: Entity,
+    pub state: PlayerState,
+}
+
+/// Sent when the player touches a hazard or falls out of the level.
+pub struct PlayerDied {
+    pub entity: Entity,
+}
+
+pub struct PlayerRespawned {
+    pub entity: Entity,
+    pub position: Vec3,
 }
 
+/// Counts down the death animation before the player is respawned.
+#[derive(Resource, Deref, DerefMut)]
+struct DeathTimer(Timer);
+
 #[derive(Component, Clone, Default)]
 pub struct PlayerFlag;
 
@@ -86,7 +187,15 @@ pub struct PlayerBundle {
 
     contact_detection: ContactDetection,
     last_safe_spot: LastSafeSpot,
+    respawn_point: RespawnPoint,
     stamina: Stamina,
+    jump_grace: JumpGrace,
+    dash_state: DashState,
+    drop_through: DropThrough,
+    carried_velocity: CarriedVelocity,
+    following_ramp: FollowingRamp,
+    interpolated_transform: InterpolatedTransform,
+    player_state: PlayerState,
     player_flag: PlayerFlag,
 }
 
@@ -94,149 +203,569 @@ pub struct PlayerPlugin;
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.register_ldtk_entity::<PlayerBundle>("Player")
+            .add_event::<PlayerStateChanged>()
+            .add_event::<MovePerformed>()
+            .add_event::<PlayerDied>()
+            .add_event::<PlayerRespawned>()
+            .init_resource::<RespawnMode>()
+            .add_system(init_movement_config.in_schedule(OnExit(GameState::Loading)))
+            .add_system(apply_movement_config.run_if(in_game))
+            .add_system(fill_stamina_on_spawn.run_if(in_game))
             .add_systems(
-                (player_movement, update_safe_spot, check_out_of_level)
-                    .in_set(OnUpdate(GameState::Playing)),
+                (
+                    player_movement,
+                    update_safe_spot,
+                    touch_hazards,
+                    check_out_of_level,
+                    start_dying,
+                )
+                    .chain()
+                    .distributive_run_if(in_state(GameState::Playing))
+                    .in_set(FixedSet::Logic)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                respawn_player
+                    .run_if(in_state(GameState::Dying))
+                    .in_set(FixedSet::Logic)
+                    .in_schedule(CoreSchedule::FixedUpdate),
             );
     }
 }
 
-fn player_movement(
-    input: Res<Input<KeyCode>>,
-    buttons: Res<Input<GamepadButton>>,
-    axes: Res<Axis<GamepadAxis>>,
-    gamepad: Option<Res<CurrentGamepad>>,
-    mut query: Query<(&mut Velocity, &ContactDetection, &mut Stamina), With<PlayerFlag>>,
+/// Takes gravity from the movement config once it has loaded, unless the platformer config
+/// overrides it.
+fn init_movement_config(
+    platformer_config: Res<PlatformerConfig>,
+    assets: Res<AllAssets>,
+    configs: Res<Assets<MovementConfig>>,
+    mut rapier_config: ResMut<RapierConfiguration>,
 ) {
-    let mut jump_pressed = input.just_pressed(KeyCode::Up) || input.just_pressed(KeyCode::Space);
-    let mut left_pressed = input.pressed(KeyCode::Left);
-    let mut right_pressed = input.pressed(KeyCode::Right);
-    let mut down_pressed = input.pressed(KeyCode::Down);
-    let mut down_just_pressed = input.just_pressed(KeyCode::Down);
-    let mut left_stick_x = 0.;
-    if let Some(gp) = gamepad {
-        let gamepad = gp.0;
-        jump_pressed ^= buttons.just_pressed(GamepadButton {
-            gamepad,
-            button_type: GamepadButtonType::East,
-        });
-        left_pressed ^= buttons.pressed(GamepadButton {
-            gamepad,
-            button_type: GamepadButtonType::DPadLeft,
-        });
-        right_pressed ^= buttons.pressed(GamepadButton {
-            gamepad,
-            button_type: GamepadButtonType::DPadRight,
-        });
-        down_pressed ^= buttons.pressed(GamepadButton {
-            gamepad,
-            button_type: GamepadButtonType::DPadDown,
-        });
-        down_just_pressed ^= buttons.just_pressed(GamepadButton {
-            gamepad,
-            button_type: GamepadButtonType::DPadDown,
-        });
-        if let Some(x) = axes.get(GamepadAxis {
-            gamepad,
-            axis_type: GamepadAxisType::LeftStickX,
-        }) {
-            if x < -0.3 {
-                left_stick_x = x;
-                left_pressed = true;
-            } else if x > 0.3 {
-                left_stick_x = x;
-                right_pressed = true;
-            } else {
-                left_stick_x = 0.;
+    if platformer_config.gravity.is_some() {
+        return;
+    }
+    if let Some(config) = configs.get(&assets.movement) {
+        rapier_config.gravity = Vec2::new(0., -config.gravity);
+    }
+}
+
+/// Picks up gravity changes when the movement config is hot-reloaded.
+fn apply_movement_config(
+    platformer_config: Res<PlatformerConfig>,
+    mut asset_events: EventReader<AssetEvent<MovementConfig>>,
+    configs: Res<Assets<MovementConfig>>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    if platformer_config.gravity.is_some() {
+        return;
+    }
+    for event in asset_events.iter() {
+        if let AssetEvent::Modified { handle } = event {
+            if let Some(config) = configs.get(handle) {
+                rapier_config.gravity = Vec2::new(0., -config.gravity);
             }
         }
+    }
+}
+
+fn fill_stamina_on_spawn(
+    movement_assets: MovementAssets,
+    mut respawn_events: EventReader<PlayerRespawned>,
+    mut player_query: Query<(Entity, &mut Stamina)>,
+) {
+    let Some((config, _)) = movement_assets.get() else {
+        return;
     };
+    let respawned: Vec<Entity> = respawn_events.iter().map(|event| event.entity).collect();
+    for (entity, mut stamina) in &mut player_query {
+        if stamina.is_added() || respawned.contains(&entity) {
+            **stamina = config.max_stamina;
+        }
+    }
+}
+
+#[derive(SystemParam)]
+pub struct MovementAssets<'w> {
+    assets: Res<'w, AllAssets>,
+    configs: Res<'w, Assets<MovementConfig>>,
+    surface_tables: Res<'w, Assets<SurfaceTable>>,
+}
+
+impl MovementAssets<'_> {
+    fn get(&self) -> Option<(&MovementConfig, &SurfaceTable)> {
+        Some((
+            self.configs.get(&self.assets.movement)?,
+            self.surface_tables.get(&self.assets.surfaces)?,
+        ))
+    }
+}
+
+pub fn player_movement(
+    fixed_time: Res<FixedTime>,
+    actions: Res<ActionState>,
+    movement_assets: MovementAssets,
+    mut state_events: EventWriter<PlayerStateChanged>,
+    mut move_events: EventWriter<MovePerformed>,
+    mut query: Query<
+        (
+            Entity,
+            &mut Velocity,
+            &Transform,
+            &Collider,
+            &ContactDetection,
+            &mut Stamina,
+            &mut JumpGrace,
+            &mut DashState,
+            &mut DropThrough,
+            &mut CarriedVelocity,
+            &mut FollowingRamp,
+            &mut PlayerState,
+        ),
+        With<PlayerFlag>,
+    >,
+) {
+    let jump_pressed = actions.just_pressed(Action::Jump);
+    let left_pressed = actions.pressed(Action::Left);
+    let right_pressed = actions.pressed(Action::Right);
+    let down_pressed = actions.pressed(Action::Down);
+    let down_just_pressed = actions.just_pressed(Action::Down);
+    let dash_pressed = actions.just_pressed(Action::Dash);
 
-    if let Ok((mut velocity, contact_detection, mut stamina)) = query.get_single_mut() {
-        let l = if left_pressed {
-            if left_stick_x != 0. {
-                (-left_stick_x * 1.3).min(1.)
+    if let (
+        Ok((
+            entity,
+            mut velocity,
+            transform,
+            collider,
+            contact_detection,
+            mut stamina,
+            mut jump_grace,
+            mut dash,
+            mut drop_through,
+            mut carried,
+            mut following_ramp,
+            mut state,
+        )),
+        Some((config, surface_table)),
+    ) = (query.get_single_mut(), movement_assets.get())
+    {
+        // the state only leaves Playing once the frame's fixed steps are done
+        if *state == PlayerState::Dead {
+            return;
+        }
+        let delta = fixed_time.period.as_secs_f32();
+        let was_on_ground = jump_grace.ground >= config.coyote_time;
+        // work with the player's own velocity, leaving the air momentum gained from a platform
+        if contact_detection.on_ground {
+            velocity.linvel -= **carried;
+        }
+        let wall_jump_allowed = |surface: Option<Surface>| {
+            surface.is_none_or(|surface| surface_table.get(surface).wall_jump)
+        };
+        let tick = |grace: f32, touching: bool| {
+            if touching {
+                config.coyote_time
             } else {
-                1.
+                (grace - delta).max(0.)
             }
+        };
+        // the ground sensor still overlaps the floor for a few steps after jumping off it
+        jump_grace.ascending &= velocity.linvel.y > RISING_SPEED;
+        jump_grace.ground = tick(
+            jump_grace.ground,
+            contact_detection.on_ground && !jump_grace.ascending,
+        );
+        jump_grace.left = tick(
+            jump_grace.left,
+            contact_detection.on_left && wall_jump_allowed(contact_detection.left_surface),
+        );
+        jump_grace.right = tick(
+            jump_grace.right,
+            contact_detection.on_right && wall_jump_allowed(contact_detection.right_surface),
+        );
+        jump_grace.buffer = if jump_pressed {
+            config.jump_buffer_time
         } else {
-            0.
+            (jump_grace.buffer - delta).max(0.)
         };
-        let r = if right_pressed {
-            if left_stick_x != 0. {
-                (left_stick_x * 1.3).min(1.)
+        **drop_through = (**drop_through - delta).max(0.);
+
+        let l = (actions.value(Action::Left) * config.stick_gain).min(1.);
+        let r = (actions.value(Action::Right) * config.stick_gain).min(1.);
+
+        let mut performed = None;
+
+        if dash.remaining > 0. {
+            dash.remaining -= delta;
+            // come out of the dash at run speed rather than sliding on at dash speed
+            let speed = if dash.remaining > 0. {
+                config.dash_speed
             } else {
-                1.
-            }
+                config.run_speed
+            };
+            velocity.linvel = Vec2::new(dash.direction * speed, 0.);
+            **following_ramp = false;
         } else {
-            0.
-        };
+            if r > 0. || l > 0. {
+                if contact_detection.on_ground && down_pressed {
+                    let crouch_limit = config.crouch_speed_factor;
+                    velocity.linvel.x =
+                        (r - l).clamp(-crouch_limit, crouch_limit) * config.run_speed;
+                } else {
+                    velocity.linvel.x = (r - l).clamp(-1., 1.) * config.run_speed;
+                }
+            } else if !contact_detection.on_ground {
+                velocity.linvel.x *= config.air_drag;
+            }
 
-        if r > 0. || l > 0. {
-            if contact_detection.on_ground && down_pressed {
-                velocity.linvel.x = (r - l).clamp(-0.5, 0.5) * PLAYER_SPEED;
+            // follow ramps rather than launching off them, but only once landed so that
+            // landing itself is not slowed down
+            if let Some(ramp) = contact_detection
+                .ramp_surface
+                .filter(|_| contact_detection.on_ground && was_on_ground && !jump_grace.rising)
+            {
+                // move the feet to where the ramp will be beneath them after this step, the body
+                // rests on whichever bottom corner is higher up the ramp
+                let (centre, half_extents) = collider_extents(collider);
+                let feet = transform.translation.y + centre.y - half_extents.y;
+                let x = transform.translation.x + centre.x + velocity.linvel.x * delta;
+                let ground = ramp
+                    .height_at(x - half_extents.x)
+                    .max(ramp.height_at(x + half_extents.x));
+                // rise a little past the crest, so the body clears the edge of the ground beyond it
+                let clearance = if ground >= ramp.high.y {
+                    CREST_CLEARANCE
+                } else {
+                    0.
+                };
+                velocity.linvel.y = (ground + clearance - feet) / delta;
+                **following_ramp = true;
             } else {
-                velocity.linvel.x = (r - l).clamp(-1., 1.) * PLAYER_SPEED;
+                // the ramp is let go of just short of its crest, what is left of the climb
+                // would otherwise throw the player up off the flat ground beyond
+                if **following_ramp && contact_detection.on_ground && !jump_grace.rising {
+                    velocity.linvel.y = velocity.linvel.y.min(0.);
+                }
+                **following_ramp = false;
             }
-        } else if !contact_detection.on_ground {
-            velocity.linvel.x *= 0.97;
-        }
 
-        if (left_pressed && contact_detection.on_left)
-            || (right_pressed && contact_detection.on_right)
-        {
-            velocity.linvel.y = velocity.linvel.y.max(-15.);
-        }
+            let clung_surface = if left_pressed && contact_detection.on_left {
+                Some(contact_detection.left_surface)
+            } else if right_pressed && contact_detection.on_right {
+                Some(contact_detection.right_surface)
+            } else {
+                None
+            };
+            if let Some(surface) = clung_surface {
+                let wall_slide_speed = surface
+                    .and_then(|surface| surface_table.get(surface).wall_slide_speed)
+                    .unwrap_or(config.wall_slide_speed);
+                velocity.linvel.y = velocity.linvel.y.max(-wall_slide_speed);
+            }
 
-        if jump_pressed {
-            if contact_detection.on_ground {
-                velocity.linvel.y = 500.;
-            } else if contact_detection.on_left {
-                velocity.linvel.y = 400.;
-                if !input.pressed(KeyCode::Left) {
-                    velocity.linvel.x = 300.;
+            if jump_grace.buffer > 0. {
+                if jump_grace.ground > 0. {
+                    velocity.linvel.y = config.ground_jump_speed;
+                    performed = Some(PlayerState::Jump);
+                } else if jump_grace.left > 0. {
+                    velocity.linvel.y = config.wall_jump_speed;
+                    if !left_pressed {
+                        velocity.linvel.x = config.wall_jump_push;
+                    }
+                    performed = Some(PlayerState::WallJump);
+                } else if jump_grace.right > 0. {
+                    velocity.linvel.y = config.wall_jump_speed;
+                    if !right_pressed {
+                        velocity.linvel.x = -config.wall_jump_push;
+                    }
+                    performed = Some(PlayerState::WallJump);
+                } else if jump_pressed && **stamina > 0 {
+                    velocity.linvel.y = config.air_jump_speed;
+                    **stamina -= 1;
+                    performed = Some(PlayerState::AirJump);
                 }
-            } else if contact_detection.on_right {
-                velocity.linvel.y = 400.;
-                if !input.pressed(KeyCode::Right) {
-                    velocity.linvel.x = -300.;
+                if performed.is_some() {
+                    *jump_grace = JumpGrace {
+                        rising: true,
+                        ascending: true,
+                        ..default()
+                    };
                 }
-            } else if **stamina > 0 {
-                velocity.linvel.y = 400.;
-                **stamina -= 1;
             }
-        }
 
-        if down_just_pressed && !contact_detection.on_ground {
-            velocity.linvel.x = 0.;
-            velocity.linvel.y = -800.;
+            if jump_grace.rising {
+                if velocity.linvel.y <= 0. {
+                    jump_grace.rising = false;
+                } else if !actions.pressed(Action::Jump) {
+                    velocity.linvel.y *= config.jump_cut;
+                    jump_grace.rising = false;
+                }
+            }
+
+            if down_just_pressed && contact_detection.on_platform {
+                **drop_through = config.drop_through_time;
+            }
+
+            if down_just_pressed && !contact_detection.on_ground {
+                velocity.linvel.x = 0.;
+                velocity.linvel.y = -config.ground_pound_speed;
+                performed = Some(PlayerState::GroundPound);
+            }
+
+            if dash_pressed && dash.available {
+                dash.direction = if r != l {
+                    (r - l).signum()
+                } else if velocity.linvel.x != 0. {
+                    velocity.linvel.x.signum()
+                } else {
+                    1.
+                };
+                dash.remaining = config.dash_time;
+                dash.available = false;
+                jump_grace.rising = false;
+                velocity.linvel = Vec2::new(dash.direction * config.dash_speed, 0.);
+                performed = Some(PlayerState::Dash);
+            }
         }
 
         if contact_detection.on_ground {
-            **stamina = MAX_STAMINA;
+            **stamina = config.max_stamina;
+            dash.available = true;
+        }
+
+        // following a ramp moves the player up without leaving the ground
+        let leaving_ground = jump_grace.rising
+            || (velocity.linvel.y > RISING_SPEED && contact_detection.ground_normal.x == 0.);
+        let grounded = contact_detection.on_ground && !leaving_ground;
+        let next_state = performed.unwrap_or_else(|| match *state {
+            PlayerState::Dash if dash.remaining > 0. => PlayerState::Dash,
+            PlayerState::GroundPound if !contact_detection.on_ground => PlayerState::GroundPound,
+            current => {
+                if grounded {
+                    if down_pressed {
+                        PlayerState::Crouch
+                    } else if r > 0. || l > 0. {
+                        PlayerState::Run
+                    } else {
+                        PlayerState::Idle
+                    }
+                } else if velocity.linvel.y <= RISING_SPEED
+                    && ((left_pressed && contact_detection.on_left)
+                        || (right_pressed && contact_detection.on_right))
+                {
+                    PlayerState::WallSlide
+                } else if velocity.linvel.y > RISING_SPEED {
+                    match current {
+                        PlayerState::Jump | PlayerState::WallJump | PlayerState::AirJump => current,
+                        _ => PlayerState::Jump,
+                    }
+                } else {
+                    PlayerState::Fall
+                }
+            }
+        });
+
+        if let Some(performed) = performed {
+            move_events.send(MovePerformed {
+                entity,
+                state: performed,
+            });
         }
+        if next_state != *state || performed.is_some() {
+            state_events.send(PlayerStateChanged {
+                entity,
+                from: *state,
+                to: next_state,
+            });
+            *state = next_state;
+        }
+
+        **carried = if contact_detection.on_ground {
+            contact_detection.ground_velocity
+        } else {
+            Vec2::ZERO
+        };
+        velocity.linvel += **carried;
     }
 }
 
 pub fn update_safe_spot(
-    mut contact_detectors_query: Query<(&ContactDetection, &mut LastSafeSpot, &Transform)>,
+    rapier_context: Res<RapierContext>,
+    hazard_query: Query<Entity, With<HazardCollider>>,
+    mut contact_detectors_query: Query<(Entity, &ContactDetection, &mut LastSafeSpot, &Transform)>,
 ) {
-    for (ContactDetection { is_stable, .. }, mut last_safe_spot, Transform { translation, .. }) in
-        &mut contact_detectors_query
+    for (
+        entity,
+        ContactDetection {
+            is_stable,
+            riding,
+            on_breakable,
+            ..
+        },
+        mut last_safe_spot,
+        Transform { translation, .. },
+    ) in &mut contact_detectors_query
     {
-        if *is_stable {
+        // a moving platform will have moved on, and a breakable cell may be gone, by the time the
+        // player respawns. Lava lies on solid ground, standing in it is stable but not safe.
+        let in_hazard = hazard_query
+            .iter()
+            .any(|hazard| rapier_context.intersection_pair(hazard, entity) == Some(true));
+        if *is_stable && !*riding && !*on_breakable && !in_hazard {
             **last_safe_spot = *translation;
         }
     }
 }
 
+fn touch_hazards(
+    rapier_context: Res<RapierContext>,
+    hazard_query: Query<Entity, With<HazardCollider>>,
+    player_query: Query<Entity, With<PlayerFlag>>,
+    mut death_events: EventWriter<PlayerDied>,
+) {
+    for player in &player_query {
+        if hazard_query
+            .iter()
+            .any(|hazard| rapier_context.intersection_pair(hazard, player) == Some(true))
+        {
+            death_events.send(PlayerDied { entity: player });
+        }
+    }
+}
+
 fn check_out_of_level(
-    mut query: Query<(&mut Transform, &mut Velocity, &LastSafeSpot), With<PlayerFlag>>,
+    progress: Res<LevelProgress>,
+    ldtk_levels: Res<Assets<LdtkLevel>>,
+    level_query: Query<(&Handle<LdtkLevel>, &GlobalTransform)>,
+    mut death_events: EventWriter<PlayerDied>,
+    query: Query<(Entity, &Transform), With<PlayerFlag>>,
 ) {
-    for (mut transform, mut velocity, last_safe_spot) in &mut query {
-        if transform.translation.y < -80. {
-            transform.translation = **last_safe_spot;
-            velocity.linvel = Vec2::new(0., 0.);
+    let spawned_levels: Vec<_> = level_query
+        .iter()
+        .filter_map(|(level_handle, level_transform)| {
+            ldtk_levels
+                .get(level_handle)
+                .map(|ldtk_level| (&ldtk_level.level, level_transform))
+        })
+        .collect();
+
+    // the selected level may still be spawning after a transition
+    if !spawned_levels
+        .iter()
+        .any(|(level, _)| progress.current.as_ref() == Some(&level.iid))
+    {
+        return;
+    }
+
+    for (entity, transform) in &query {
+        let position = transform.translation.truncate();
+        let in_level = spawned_levels.iter().any(|(level, level_transform)| {
+            level_kill_bounds(level, level_transform).contains(position)
+        });
+        if !in_level {
+            death_events.send(PlayerDied { entity });
         }
     }
 }
+
+/// Freezes a dead player in place and ignores input until [respawn_player] brings them back.
+fn start_dying(
+    mut commands: Commands,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut death_events: EventReader<PlayerDied>,
+    mut state_events: EventWriter<PlayerStateChanged>,
+    mut query: Query<(&mut Velocity, &mut DashState, &mut PlayerState), With<PlayerFlag>>,
+) {
+    for PlayerDied { entity } in death_events.iter() {
+        if let Ok((mut velocity, mut dash, mut state)) = query.get_mut(*entity) {
+            if *state == PlayerState::Dead {
+                continue;
+            }
+            velocity.linvel = Vec2::ZERO;
+            dash.remaining = 0.;
+            state_events.send(PlayerStateChanged {
+                entity: *entity,
+                from: *state,
+                to: PlayerState::Dead,
+            });
+            *state = PlayerState::Dead;
+            commands.entity(*entity).insert(RigidBodyDisabled);
+            commands.insert_resource(DeathTimer(Timer::from_seconds(DEATH_TIME, TimerMode::Once)));
+            next_state.set(GameState::Dying);
+        }
+    }
+}
+
+#[derive(SystemParam)]
+struct RespawnEvents<'w> {
+    state_changed: EventWriter<'w, PlayerStateChanged>,
+    respawned: EventWriter<'w, PlayerRespawned>,
+}
+
+fn respawn_player(
+    mut commands: Commands,
+    fixed_time: Res<FixedTime>,
+    respawn_mode: Res<RespawnMode>,
+    death_timer: Option<ResMut<DeathTimer>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut events: RespawnEvents,
+    mut query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Velocity,
+            &mut JumpGrace,
+            &mut CarriedVelocity,
+            &mut PlayerState,
+            &LastSafeSpot,
+            &RespawnPoint,
+        ),
+        With<PlayerFlag>,
+    >,
+) {
+    // removed by an earlier fixed step of the frame that respawned the player
+    let Some(mut death_timer) = death_timer else {
+        return;
+    };
+    if !death_timer.tick(fixed_time.period).just_finished() {
+        return;
+    }
+
+    for (
+        entity,
+        mut transform,
+        mut velocity,
+        mut jump_grace,
+        mut carried,
+        mut state,
+        last_safe_spot,
+        respawn_point,
+    ) in &mut query
+    {
+        if *state != PlayerState::Dead {
+            continue;
+        }
+        let position = match *respawn_mode {
+            RespawnMode::Checkpoint => respawn_point.unwrap_or(**last_safe_spot),
+            RespawnMode::LastSafeSpot => **last_safe_spot,
+        };
+        transform.translation = position.truncate().extend(transform.translation.z);
+        velocity.linvel = Vec2::ZERO;
+        *jump_grace = JumpGrace::default();
+        **carried = Vec2::ZERO;
+        events.state_changed.send(PlayerStateChanged {
+            entity,
+            from: *state,
+            to: PlayerState::Idle,
+        });
+        *state = PlayerState::Idle;
+        commands.entity(entity).remove::<RigidBodyDisabled>();
+        events.respawned.send(PlayerRespawned {
+            entity,
+            position: transform.translation,
+        });
+    }
+
+    commands.remove_resource::<DeathTimer>();
+    next_state.set(GameState::Playing);
+}
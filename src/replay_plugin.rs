@@ -0,0 +1,278 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy::time::{TimeSystem, TimeUpdateStrategy};
+use bevy_ecs_ldtk::prelude::*;
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    controls_plugin::{ActionState, Paused},
+    in_game,
+    loading_plugin::AllAssets,
+    physics_plugin::FixedSet,
+    player_plugin::player_movement,
+    GameState,
+};
+
+/// Random numbers for gameplay. Reseeded when the game starts so replays see the same numbers.
+#[derive(Resource, Deref, DerefMut)]
+pub struct GameRng(StdRng);
+
+impl GameRng {
+    pub fn seeded(seed: u64) -> Self {
+        GameRng(StdRng::seed_from_u64(seed))
+    }
+}
+
+/// Whether the run is recorded to, or played back from, a replay file. Read by [ReplayPlugin] when
+/// it is built, so it has to be inserted before the game's plugins.
+#[derive(Resource, Clone, Default, Debug, PartialEq, Eq)]
+pub enum ReplayMode {
+    #[default]
+    Off,
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ReplayHeader {
+    /// Iid of the level the run starts in.
+    pub level: String,
+    pub seed: u64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    pub delta: Duration,
+    /// Actions seen by player movement in each fixed step run during the frame.
+    pub steps: Vec<ActionState>,
+}
+
+/// A recorded run. Frame times are played back as recorded, so every frame runs the same fixed
+/// steps with the same inputs as it did while recording.
+#[derive(Resource, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Replay {
+    pub header: ReplayHeader,
+    pub frames: VecDeque<ReplayFrame>,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Option<Self> {
+        std::fs::read_to_string(path)
+            .map_err(|error| error.to_string())
+            .and_then(|contents| ron::from_str(&contents).map_err(|error| error.to_string()))
+            .map_err(|error| warn!("could not load replay {}: {error}", path.display()))
+            .ok()
+    }
+
+    pub fn save(&self, path: &Path) {
+        let result = ron::to_string(self)
+            .map_err(|error| error.to_string())
+            .and_then(|contents| std::fs::write(path, contents).map_err(|error| error.to_string()));
+        match result {
+            Ok(()) => info!("saved replay to {}", path.display()),
+            Err(error) => warn!("could not save replay {}: {error}", path.display()),
+        }
+    }
+}
+
+/// Frame being played back and how many of its steps have run.
+#[derive(Resource, Default)]
+struct ReplayCursor {
+    started: bool,
+    frame: Option<ReplayFrame>,
+    step: usize,
+}
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        let mode = app
+            .world
+            .get_resource::<ReplayMode>()
+            .cloned()
+            .unwrap_or_default();
+        let (mode, replay) = match mode {
+            ReplayMode::Replay(path) => match Replay::load(&path) {
+                Some(replay) => (ReplayMode::Replay(path), replay),
+                None => (ReplayMode::Off, Replay::default()),
+            },
+            mode => (mode, Replay::default()),
+        };
+
+        app.insert_resource(mode)
+            .insert_resource(replay)
+            .insert_resource(GameRng::seeded(rand::random()))
+            .init_resource::<ReplayCursor>()
+            .add_system(start_replay.in_schedule(OnExit(GameState::Loading)))
+            .add_system(
+                begin_replay_frame
+                    .run_if(in_game)
+                    .in_base_set(CoreSet::First)
+                    .after(TimeSystem),
+            )
+            .add_system(
+                replay_inputs
+                    .before(player_movement)
+                    .run_if(in_state(GameState::Playing))
+                    .in_set(FixedSet::Logic)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                schedule_replay_time
+                    .run_if(in_game)
+                    .run_if(replaying)
+                    .in_base_set(CoreSet::Last),
+            )
+            .add_system(save_replay.run_if(recording).in_base_set(CoreSet::Last));
+    }
+}
+
+fn recording(mode: Res<ReplayMode>) -> bool {
+    matches!(*mode, ReplayMode::Record(_))
+}
+
+fn replaying(mode: Res<ReplayMode>) -> bool {
+    matches!(*mode, ReplayMode::Replay(_))
+}
+
+/// Fills in the header when recording, or puts the level and seed of the header back in place
+/// when playing back. A replay starting in a level that does not exist is dropped, and the game
+/// runs as if it was never asked for.
+fn start_replay(
+    mut mode: ResMut<ReplayMode>,
+    assets: Res<AllAssets>,
+    ldtk_assets: Res<Assets<LdtkAsset>>,
+    mut level_selection: ResMut<LevelSelection>,
+    mut replay: ResMut<Replay>,
+    mut cursor: ResMut<ReplayCursor>,
+    mut rng: ResMut<GameRng>,
+) {
+    let ldtk_asset = ldtk_assets.get(&assets.level0);
+    if let ReplayMode::Replay(path) = &*mode {
+        let replay_level = LevelSelection::Iid(replay.header.level.clone());
+        if ldtk_asset.is_some_and(|ldtk_asset| ldtk_asset.get_level(&replay_level).is_some()) {
+            *level_selection = replay_level;
+        } else {
+            warn!(
+                "replay {} starts in unknown level {}, playing without it",
+                path.display(),
+                replay.header.level
+            );
+            *mode = ReplayMode::Off;
+        }
+    }
+    if !matches!(*mode, ReplayMode::Replay(_)) {
+        replay.header = ReplayHeader {
+            level: ldtk_asset
+                .and_then(|ldtk_asset| ldtk_asset.get_level(&level_selection))
+                .map(|level| level.iid.clone())
+                .unwrap_or_default(),
+            seed: rand::random(),
+        };
+        replay.frames.clear();
+    }
+    *rng = GameRng::seeded(replay.header.seed);
+    *cursor = ReplayCursor::default();
+}
+
+fn begin_replay_frame(
+    time: Res<Time>,
+    paused: Res<Paused>,
+    mut mode: ResMut<ReplayMode>,
+    mut replay: ResMut<Replay>,
+    mut cursor: ResMut<ReplayCursor>,
+    mut fixed_time: ResMut<FixedTime>,
+) {
+    // frames spent paused run no fixed steps, and are left out as pausing is not replayed
+    if *mode == ReplayMode::Off || paused.0 {
+        return;
+    }
+    if !cursor.started {
+        // drop the time left over from loading, so the fixed steps line up with the recording
+        *fixed_time = FixedTime::new(fixed_time.period);
+        cursor.started = true;
+    }
+
+    match *mode {
+        ReplayMode::Record(_) => replay.frames.push_back(ReplayFrame {
+            // real time, which keeps going while the game time is paused for hitstop
+            delta: time.raw_delta(),
+            steps: Vec::new(),
+        }),
+        ReplayMode::Replay(_) => {
+            if let Some(frame) = cursor.frame.take() {
+                if frame.steps.len() != cursor.step {
+                    warn!(
+                        "replay out of sync, frame recorded {} steps but ran {}",
+                        frame.steps.len(),
+                        cursor.step
+                    );
+                }
+            }
+            cursor.frame = replay.frames.pop_front();
+            cursor.step = 0;
+            if cursor.frame.is_none() {
+                info!("replay finished");
+                *mode = ReplayMode::Off;
+            }
+        }
+        ReplayMode::Off => (),
+    }
+}
+
+/// Stores the actions of every fixed step while recording, and replaces them with the recorded
+/// ones while playing back.
+fn replay_inputs(
+    mode: Res<ReplayMode>,
+    mut actions: ResMut<ActionState>,
+    mut replay: ResMut<Replay>,
+    mut cursor: ResMut<ReplayCursor>,
+) {
+    match *mode {
+        ReplayMode::Record(_) => {
+            if let Some(frame) = replay.frames.back_mut() {
+                frame.steps.push(actions.snapshot());
+            }
+        }
+        ReplayMode::Replay(_) => {
+            let cursor = &mut *cursor;
+            *actions = cursor
+                .frame
+                .as_ref()
+                .and_then(|frame| frame.steps.get(cursor.step))
+                .cloned()
+                .unwrap_or_default();
+            cursor.step += 1;
+        }
+        ReplayMode::Off => (),
+    }
+}
+
+/// Makes the next frame as long as the recorded one.
+fn schedule_replay_time(
+    time: Res<Time>,
+    replay: Res<Replay>,
+    mut update_strategy: ResMut<TimeUpdateStrategy>,
+) {
+    *update_strategy = match (time.last_update(), replay.frames.front()) {
+        (Some(last_update), Some(frame)) => {
+            TimeUpdateStrategy::ManualInstant(last_update + frame.delta)
+        }
+        _ => TimeUpdateStrategy::Automatic,
+    };
+}
+
+fn save_replay(mode: Res<ReplayMode>, replay: Res<Replay>, mut exit_events: EventReader<AppExit>) {
+    if exit_events.iter().count() == 0 {
+        return;
+    }
+    if let ReplayMode::Record(path) = &*mode {
+        replay.save(path);
+    }
+}
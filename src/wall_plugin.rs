@@ -1,55 +1,459 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::Hash;
+use std::marker::PhantomData;
 
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
 use bevy_ecs_ldtk::prelude::*;
 use bevy_rapier2d::prelude::*;
+use bevy_rapier2d::rapier::math::Vector;
+use serde::{Deserialize, Serialize};
 
-use crate::GameState;
+use crate::{in_game, loading_plugin::AllAssets, physics_plugin::FixedSet};
+
+// IntGrid values of the Collisions layer
+pub const WALL: i32 = 1;
+pub const SPIKES: i32 = 2;
+pub const LAVA: i32 = 3;
+pub const ONE_WAY_PLATFORM: i32 = 4;
+pub const ICE: i32 = 5;
+pub const STICKY: i32 = 6;
+pub const BOUNCY: i32 = 7;
+pub const SLOPE_UP_RIGHT: i32 = 8;
+pub const SLOPE_UP_LEFT: i32 = 9;
+pub const GENTLE_SLOPE_UP_RIGHT_LOW: i32 = 10;
+pub const GENTLE_SLOPE_UP_RIGHT_HIGH: i32 = 11;
+pub const GENTLE_SLOPE_UP_LEFT_HIGH: i32 = 12;
+pub const GENTLE_SLOPE_UP_LEFT_LOW: i32 = 13;
+pub const CRUMBLING_WALL: i32 = 14;
+pub const BREAKABLE_WALL: i32 = 15;
+
+/// Largest angle between a contact normal and straight up that a one-way platform still blocks.
+const ONE_WAY_ALLOWED_ANGLE: f32 = std::f32::consts::FRAC_PI_4;
+/// How far below a one-way platform's top the feet may be while still standing on it.
+const ONE_WAY_TOLERANCE: f32 = 1.;
+/// How far above the feet a ramp's top must be for the ramp to count as ground ahead rather than
+/// one the feet have already crested.
+const CREST_TOLERANCE: f32 = 1.;
+/// Upward speed above which a detector is rising, such as when passing up through a one-way
+/// platform, rather than resting with the solver's leftover noise.
+pub const RISING_SPEED: f32 = 1.;
 
 pub struct WallPlugin;
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
-pub struct Wall;
+/// Material of a wall, looked up in the [SurfaceTable] for its physical properties.
+#[derive(
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Debug,
+    Default,
+    Hash,
+    Component,
+    Serialize,
+    Deserialize,
+)]
+pub enum Surface {
+    #[default]
+    Normal,
+    Ice,
+    Sticky,
+    Bouncy,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SurfaceProperties {
+    pub friction: f32,
+    pub restitution: f32,
+    /// Overrides the movement config's wall slide speed while clinging to this surface.
+    pub wall_slide_speed: Option<f32>,
+    pub wall_jump: bool,
+}
+
+impl Default for SurfaceProperties {
+    fn default() -> Self {
+        Self {
+            friction: 1.,
+            restitution: 0.,
+            wall_slide_speed: None,
+            wall_jump: true,
+        }
+    }
+}
+
+/// Properties of every surface material, loaded from a `.surfaces.ron` asset.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TypeUuid)]
+#[uuid = "0d9f4b7e-2c6a-4e1f-b3d8-7a5c9e2f1b64"]
+pub struct SurfaceTable {
+    pub surfaces: HashMap<Surface, SurfaceProperties>,
+}
+
+impl SurfaceTable {
+    pub fn get(&self, surface: Surface) -> SurfaceProperties {
+        self.surfaces.get(&surface).cloned().unwrap_or_default()
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Hash, Component)]
+pub struct Wall {
+    pub surface: Surface,
+}
+
+impl From<IntGridCell> for Wall {
+    fn from(int_grid_cell: IntGridCell) -> Wall {
+        let surface = match int_grid_cell.value {
+            ICE => Surface::Ice,
+            STICKY => Surface::Sticky,
+            BOUNCY => Surface::Bouncy,
+            _ => Surface::Normal,
+        };
+        Wall { surface }
+    }
+}
 
 #[derive(Clone, Debug, Default, Bundle, LdtkIntCell)]
 pub struct WallBundle {
+    #[from_int_grid_cell]
     wall: Wall,
 }
 
+/// Spikes, lava and other cells that kill the player on touch.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Hash, Component)]
+pub struct Hazard;
+
+#[derive(Clone, Debug, Default, Bundle, LdtkIntCell)]
+pub struct HazardBundle {
+    hazard: Hazard,
+}
+
+/// Sensor covering a merged rectangle of hazard cells.
+#[derive(Component)]
+pub struct HazardCollider;
+
+/// Platform cells that can be jumped up through and dropped down from.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Hash, Component)]
+pub struct OneWayPlatform;
+
+#[derive(Clone, Debug, Default, Bundle, LdtkIntCell)]
+pub struct OneWayPlatformBundle {
+    one_way_platform: OneWayPlatform,
+}
+
+/// Collider covering a merged rectangle of one-way platform cells, solid only from above.
+#[derive(Component)]
+pub struct OneWayPlatformCollider;
+
+/// Ramp cells. Steep ramps rise one cell per cell, gentle ramps rise half a cell per cell and
+/// come in low and high halves that are placed next to each other.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Hash, Component)]
+pub enum Slope {
+    #[default]
+    UpRight,
+    UpLeft,
+    GentleUpRightLow,
+    GentleUpRightHigh,
+    GentleUpLeftHigh,
+    GentleUpLeftLow,
+}
+
+impl Slope {
+    /// Rise of the surface per cell, and height of the surface above the cell's bottom at the
+    /// cell's left edge, both in cells.
+    fn profile(&self) -> (f32, f32) {
+        match self {
+            Slope::UpRight => (1., 0.),
+            Slope::UpLeft => (-1., 1.),
+            Slope::GentleUpRightLow => (0.5, 0.),
+            Slope::GentleUpRightHigh => (0.5, 0.5),
+            Slope::GentleUpLeftHigh => (-0.5, 1.),
+            Slope::GentleUpLeftLow => (-0.5, 0.5),
+        }
+    }
+}
+
+impl From<IntGridCell> for Slope {
+    fn from(int_grid_cell: IntGridCell) -> Slope {
+        match int_grid_cell.value {
+            SLOPE_UP_LEFT => Slope::UpLeft,
+            GENTLE_SLOPE_UP_RIGHT_LOW => Slope::GentleUpRightLow,
+            GENTLE_SLOPE_UP_RIGHT_HIGH => Slope::GentleUpRightHigh,
+            GENTLE_SLOPE_UP_LEFT_HIGH => Slope::GentleUpLeftHigh,
+            GENTLE_SLOPE_UP_LEFT_LOW => Slope::GentleUpLeftLow,
+            _ => Slope::UpRight,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Bundle, LdtkIntCell)]
+pub struct SlopeBundle {
+    #[from_int_grid_cell]
+    slope: Slope,
+}
+
+/// Convex collider covering a straight run of ramp cells.
+#[derive(Component, Clone, Debug)]
+pub struct SlopeCollider {
+    pub normal: Vec2,
+    /// Ramp surface relative to the collider's origin.
+    pub surface: RampSurface,
+}
+
+/// Straight top edge of a ramp, between its lower and upper ends.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RampSurface {
+    pub low: Vec2,
+    pub high: Vec2,
+}
+
+impl RampSurface {
+    fn translated(self, offset: Vec2) -> RampSurface {
+        RampSurface {
+            low: self.low + offset,
+            high: self.high + offset,
+        }
+    }
+
+    /// Height of the surface above `x`, level with the nearest end beyond either end.
+    pub fn height_at(&self, x: f32) -> f32 {
+        let t = ((x - self.low.x) / (self.high.x - self.low.x)).clamp(0., 1.);
+        self.low.y + (self.high.y - self.low.y) * t
+    }
+}
+
+/// Seconds remaining in which the entity falls through one-way platforms.
+#[derive(Component, Clone, Default, Debug, Deref, DerefMut)]
+pub struct DropThrough(pub f32);
+
+/// IntGrid cell kinds whose cells are merged into one entity per rectangle. Only neighbouring
+/// cells with equal components are merged together.
+pub trait MergedCell: Component + Copy + Eq + Hash {
+    type Bundle: Bundle;
+
+    fn rect_bundle(&self, rect: &CellRect, grid_size: i32) -> Self::Bundle;
+}
+
+/// Cells covered by a merged collider, used to rebuild only the colliders around changed cells.
+#[derive(Component, Clone, Debug)]
+pub struct MergedCollider<T: MergedCell> {
+    pub rect: CellRect,
+    marker: PhantomData<T>,
+}
+
+/// Friction and restitution are filled in from the [SurfaceTable] by [apply_surface_properties].
+impl MergedCell for Wall {
+    type Bundle = (Collider, RigidBody, Surface, Transform, GlobalTransform);
+
+    fn rect_bundle(&self, rect: &CellRect, grid_size: i32) -> Self::Bundle {
+        (
+            rect.collider(grid_size),
+            RigidBody::Fixed,
+            self.surface,
+            rect.transform(grid_size),
+            GlobalTransform::default(),
+        )
+    }
+}
+
+impl MergedCell for Hazard {
+    type Bundle = (Collider, Sensor, Transform, GlobalTransform, HazardCollider);
+
+    fn rect_bundle(&self, rect: &CellRect, grid_size: i32) -> Self::Bundle {
+        (
+            rect.collider(grid_size),
+            Sensor,
+            rect.transform(grid_size),
+            GlobalTransform::default(),
+            HazardCollider,
+        )
+    }
+}
+
+impl MergedCell for OneWayPlatform {
+    type Bundle = (
+        Collider,
+        RigidBody,
+        Friction,
+        ActiveHooks,
+        Transform,
+        GlobalTransform,
+        OneWayPlatformCollider,
+    );
+
+    fn rect_bundle(&self, rect: &CellRect, grid_size: i32) -> Self::Bundle {
+        (
+            rect.collider(grid_size),
+            RigidBody::Fixed,
+            Friction::new(1.0),
+            ActiveHooks::MODIFY_SOLVER_CONTACTS,
+            rect.transform(grid_size),
+            GlobalTransform::default(),
+            OneWayPlatformCollider,
+        )
+    }
+}
+
+#[derive(SystemParam)]
+pub struct OneWayPlatformHooks<'w, 's> {
+    platform_query: Query<'w, 's, (), With<OneWayPlatformCollider>>,
+    drop_through_query: Query<'w, 's, &'static DropThrough>,
+}
+
+impl BevyPhysicsHooks for OneWayPlatformHooks<'_, '_> {
+    fn modify_solver_contacts(&self, context: ContactModificationContextView) {
+        let (allowed_normal, other_body) = if self.platform_query.contains(context.collider1()) {
+            (Vector::y(), context.rigid_body2())
+        } else if self.platform_query.contains(context.collider2()) {
+            (-Vector::y(), context.rigid_body1())
+        } else {
+            return;
+        };
+
+        let dropping = other_body
+            .and_then(|body| self.drop_through_query.get(body).ok())
+            .is_some_and(|drop_through| **drop_through > 0.);
+
+        if dropping {
+            context.raw.solver_contacts.clear();
+        } else {
+            context
+                .raw
+                .update_as_oneway_platform(&allowed_normal, ONE_WAY_ALLOWED_ANGLE);
+        }
+    }
+}
+
 impl Plugin for WallPlugin {
     fn build(&self, app: &mut App) {
-        app.register_ldtk_int_cell::<WallBundle>(1).add_systems(
-            (
-                spawn_wall_collision,
-                spawn_sensors,
-                contact_detection,
-                update_contact_detectors,
+        app.register_ldtk_int_cell::<WallBundle>(WALL)
+            .register_ldtk_int_cell::<WallBundle>(ICE)
+            .register_ldtk_int_cell::<WallBundle>(STICKY)
+            .register_ldtk_int_cell::<WallBundle>(BOUNCY)
+            .register_ldtk_int_cell::<HazardBundle>(SPIKES)
+            .register_ldtk_int_cell::<HazardBundle>(LAVA)
+            .register_ldtk_int_cell::<OneWayPlatformBundle>(ONE_WAY_PLATFORM)
+            .register_ldtk_int_cell::<SlopeBundle>(SLOPE_UP_RIGHT)
+            .register_ldtk_int_cell::<SlopeBundle>(SLOPE_UP_LEFT)
+            .register_ldtk_int_cell::<SlopeBundle>(GENTLE_SLOPE_UP_RIGHT_LOW)
+            .register_ldtk_int_cell::<SlopeBundle>(GENTLE_SLOPE_UP_RIGHT_HIGH)
+            .register_ldtk_int_cell::<SlopeBundle>(GENTLE_SLOPE_UP_LEFT_HIGH)
+            .register_ldtk_int_cell::<SlopeBundle>(GENTLE_SLOPE_UP_LEFT_LOW)
+            .add_event::<Landed>()
+            .add_event::<LeftGround>()
+            .add_event::<TouchedWall>()
+            .add_event::<BecameStable>()
+            .add_systems(
+                (
+                    spawn_cell_collision::<Wall>,
+                    spawn_cell_collision::<Hazard>,
+                    spawn_cell_collision::<OneWayPlatform>,
+                    spawn_slope_collision,
+                    apply_surface_properties,
+                    spawn_sensors,
+                )
+                    .distributive_run_if(in_game),
             )
-                .in_set(OnUpdate(GameState::Playing)),
-        );
+            .add_systems(
+                (
+                    contact_detection,
+                    update_contact_detectors,
+                    update_ground_detection,
+                )
+                    .chain()
+                    .distributive_run_if(in_game)
+                    .in_set(FixedSet::Contacts)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            );
     }
 }
 
-#[derive(Component)]
-pub struct ContactSensor {
-    pub detecting_entity: Entity,
-    pub intersecting_entities: HashSet<Entity>,
+/// Side of a contact detector watched by one of its sensors.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub enum ContactSide {
+    Left,
+    Right,
+    Ground,
+    /// Bottom corners, both touching ground means the detector is not hanging over an edge.
+    StableLeft,
+    StableRight,
 }
 
-#[derive(Component)]
-pub struct ContactSensorLeft;
-
-#[derive(Component)]
-pub struct ContactSensorRight;
+/// Cuboid sensor placed around a detector, sized and positioned relative to the half extents of
+/// the detector's bounding box.
+#[derive(Clone, Debug)]
+pub struct SensorShape {
+    pub side: ContactSide,
+    pub half_extents: Vec2,
+    pub offset: Vec2,
+    /// Extra offset in pixels, applied after scaling `offset`.
+    pub nudge: Vec2,
+}
 
-#[derive(Component)]
-pub struct ContactSensorGround;
+/// Sensors spawned for a [ContactDetection] entity. Entities without one get the default
+/// sensors, which are tuned for the player.
+#[derive(Component, Clone, Debug)]
+pub struct ContactSensorConfig {
+    pub sensors: Vec<SensorShape>,
+    /// Scale of the frictionless copy of the detector's collider, which keeps it from sticking
+    /// to walls. `None` spawns no such collider.
+    pub friction_scale: Option<Vec2>,
+}
 
-#[derive(Component)]
-pub struct ContactSensorStableLeft;
+impl Default for ContactSensorConfig {
+    fn default() -> Self {
+        let sensor = |side, half_extents, offset, nudge| SensorShape {
+            side,
+            half_extents,
+            offset,
+            nudge,
+        };
+        Self {
+            sensors: vec![
+                sensor(
+                    ContactSide::Left,
+                    Vec2::new(0.5, 0.9),
+                    Vec2::new(-1.1, 0.),
+                    Vec2::ZERO,
+                ),
+                sensor(
+                    ContactSide::Right,
+                    Vec2::new(0.5, 0.9),
+                    Vec2::new(1.1, 0.),
+                    Vec2::ZERO,
+                ),
+                sensor(
+                    ContactSide::Ground,
+                    Vec2::new(0.9, 0.5),
+                    Vec2::new(0., -1.),
+                    Vec2::ZERO,
+                ),
+                sensor(
+                    ContactSide::StableLeft,
+                    Vec2::new(0.125, 0.5),
+                    Vec2::new(-1., -1.),
+                    Vec2::new(1., 0.),
+                ),
+                sensor(
+                    ContactSide::StableRight,
+                    Vec2::new(0.125, 0.5),
+                    Vec2::new(1., -1.),
+                    Vec2::new(-1., 0.),
+                ),
+            ],
+            friction_scale: Some(Vec2::new(1.02, 0.99)),
+        }
+    }
+}
 
 #[derive(Component)]
-pub struct ContactSensorStableRight;
+pub struct ContactSensor {
+    pub side: ContactSide,
+    pub detecting_entity: Entity,
+    pub intersecting_entities: HashSet<Entity>,
+}
 
 #[derive(Component)]
 pub struct FrictionCollider;
@@ -59,246 +463,512 @@ pub struct ContactDetection {
     pub on_left: bool,
     pub on_right: bool,
     pub on_ground: bool,
+    /// Upward normal of the ground, or of a ramp just ahead, `Vec2::Y` on flat ground and zero in
+    /// the air.
+    pub ground_normal: Vec2,
+    /// Surface of the ramp underfoot or just ahead, if any.
+    pub ramp_surface: Option<RampSurface>,
+    /// Standing on a kinematic body such as a moving platform, which moves at `ground_velocity`.
+    pub riding: bool,
+    pub ground_velocity: Vec2,
+    /// Surfaces currently touched on each side, `None` when not touching a wall surface.
+    pub left_surface: Option<Surface>,
+    pub right_surface: Option<Surface>,
+    pub ground_surface: Option<Surface>,
+    /// Standing on a one-way platform that can be dropped through.
+    pub on_platform: bool,
+    /// Standing on a cell that crumbles or can be broken, kept up to date by the breakable plugin.
+    pub on_breakable: bool,
     pub stable_left: bool,
     pub stable_right: bool,
     pub is_stable: bool,
+    /// Entities overlapping the sensor on each side.
+    pub contacts: HashMap<ContactSide, HashSet<Entity>>,
 }
 
-pub fn spawn_wall_collision(
-    mut commands: Commands,
-    wall_query: Query<(&GridCoords, &Parent), Added<Wall>>,
-    parent_query: Query<&Parent, Without<Wall>>,
-    level_query: Query<(Entity, &Handle<LdtkLevel>)>,
-    levels: Res<Assets<LdtkLevel>>,
-) {
+/// Sent when a contact detector reaches the ground, with its velocity just before the impact.
+pub struct Landed {
+    pub entity: Entity,
+    pub impact_velocity: Vec2,
+}
+
+pub struct LeftGround {
+    pub entity: Entity,
+}
+
+/// Sent when a contact detector starts touching a wall on the left or right.
+pub struct TouchedWall {
+    pub entity: Entity,
+    pub side: ContactSide,
+}
+
+/// Sent when both stability sensors of a contact detector touch ground.
+pub struct BecameStable {
+    pub entity: Entity,
+}
+
+impl ContactDetection {
+    pub fn touching(&self, side: ContactSide) -> impl Iterator<Item = Entity> + '_ {
+        self.contacts.get(&side).into_iter().flatten().copied()
+    }
+}
+
+/// Centre and half extents of a collider's bounding box, relative to its entity.
+pub fn collider_extents(collider: &Collider) -> (Vec2, Vec2) {
+    let aabb = collider.raw.compute_local_aabb();
+    let (centre, half_extents) = (aabb.center(), aabb.half_extents());
+    (
+        Vec2::new(centre.x, centre.y),
+        Vec2::new(half_extents.x, half_extents.y),
+    )
+}
+
+/// Inclusive range of grid cells covered by one merged collider.
+#[derive(Clone, Debug)]
+pub struct CellRect {
+    pub left: i32,
+    pub right: i32,
+    pub top: i32,
+    pub bottom: i32,
+}
+
+impl CellRect {
+    pub fn collider(&self, grid_size: i32) -> Collider {
+        Collider::cuboid(
+            (self.right as f32 - self.left as f32 + 1.) * grid_size as f32 / 2.,
+            (self.top as f32 - self.bottom as f32 + 1.) * grid_size as f32 / 2.,
+        )
+    }
+
+    pub fn transform(&self, grid_size: i32) -> Transform {
+        Transform::from_xyz(
+            (self.left + self.right + 1) as f32 * grid_size as f32 / 2.,
+            (self.bottom + self.top + 1) as f32 * grid_size as f32 / 2.,
+            0.,
+        )
+    }
+
+    /// Whether the cell is inside the rectangle or next to it, including diagonally.
+    pub fn touches(&self, grid_coords: &GridCoords) -> bool {
+        (self.left - 1..=self.right + 1).contains(&grid_coords.x)
+            && (self.bottom - 1..=self.top + 1).contains(&grid_coords.y)
+    }
+
+    pub fn contains(&self, grid_coords: &GridCoords) -> bool {
+        (self.left..=self.right).contains(&grid_coords.x)
+            && (self.bottom..=self.top).contains(&grid_coords.y)
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = GridCoords> + '_ {
+        (self.bottom..=self.top)
+            .flat_map(move |y| (self.left..=self.right).map(move |x| GridCoords { x, y }))
+    }
+}
+
+/// Merges cells into rectangles by joining each row into plates, then stacking plates with the
+/// same extent on consecutive rows.
+pub fn merge_cells(cells: &HashSet<GridCoords>, width: i32, height: i32) -> Vec<CellRect> {
     #[derive(Clone, Eq, PartialEq, Debug, Default, Hash)]
     struct Plate {
         left: i32,
         right: i32,
     }
 
-    struct Rect {
-        left: i32,
-        right: i32,
-        top: i32,
-        bottom: i32,
+    let mut plate_stack: Vec<Vec<Plate>> = Vec::new();
+
+    for y in 0..height {
+        let mut row_plates: Vec<Plate> = Vec::new();
+        let mut plate_start = None;
+
+        for x in 0..width + 1 {
+            match (plate_start, cells.contains(&GridCoords { x, y })) {
+                (Some(s), false) => {
+                    row_plates.push(Plate {
+                        left: s,
+                        right: x - 1,
+                    });
+                    plate_start = None;
+                }
+                (None, true) => plate_start = Some(x),
+                _ => (),
+            }
+        }
+
+        plate_stack.push(row_plates);
+    }
+
+    let mut rect_builder: HashMap<Plate, CellRect> = HashMap::new();
+    let mut prev_row: Vec<Plate> = Vec::new();
+    let mut rects: Vec<CellRect> = Vec::new();
+
+    plate_stack.push(Vec::new());
+
+    for (y, current_row) in plate_stack.into_iter().enumerate() {
+        for prev_plate in &prev_row {
+            if !current_row.contains(prev_plate) {
+                if let Some(rect) = rect_builder.remove(prev_plate) {
+                    rects.push(rect);
+                }
+            }
+        }
+        for plate in &current_row {
+            rect_builder
+                .entry(plate.clone())
+                .and_modify(|e| e.top += 1)
+                .or_insert(CellRect {
+                    bottom: y as i32,
+                    top: y as i32,
+                    left: plate.left,
+                    right: plate.right,
+                });
+        }
+        prev_row = current_row;
+    }
+
+    rects
+}
+
+/// Finds the level an IntGrid cell belongs to and the size of that level's grid.
+#[derive(SystemParam)]
+pub struct LevelGrids<'w, 's> {
+    parent_query: Query<'w, 's, &'static Parent>,
+    level_query: Query<'w, 's, &'static Handle<LdtkLevel>>,
+    levels: Res<'w, Assets<LdtkLevel>>,
+}
+
+impl LevelGrids<'_, '_> {
+    /// The level entity owning the layer that is the parent of a cell.
+    fn level_of(&self, cell_parent: &Parent) -> Option<Entity> {
+        self.parent_query
+            .get(cell_parent.get())
+            .ok()
+            .map(|grandparent| grandparent.get())
+    }
+
+    fn level(&self, level_entity: Entity) -> Option<&LdtkLevel> {
+        self.level_query.get(level_entity).ok().map(|level_handle| {
+            self.levels
+                .get(level_handle)
+                .expect("level should be loaded")
+        })
     }
 
-    let mut level_to_wall_locations: HashMap<Entity, HashSet<GridCoords>> = HashMap::new();
+    /// Iid of a spawned level. Levels are spawned in no particular order, so unlike their entities
+    /// it can order them the same way every run.
+    fn iid(&self, level_entity: Entity) -> Option<&str> {
+        self.level(level_entity)
+            .map(|level| level.level.iid.as_str())
+    }
 
-    wall_query.for_each(|(&grid_coords, parent)| {
-        if let Ok(grandparent) = parent_query.get(parent.get()) {
-            level_to_wall_locations
-                .entry(grandparent.get())
+    /// Width and height in cells, and cell size in pixels, of a spawned level's grid.
+    pub(crate) fn grid(&self, level_entity: Entity) -> Option<(i32, i32, i32)> {
+        let level = self.level(level_entity)?;
+        let LayerInstance {
+            c_wid,
+            c_hei,
+            grid_size,
+            ..
+        } = level
+            .level
+            .layer_instances
+            .as_ref()
+            .expect("level asset should have layers")[0];
+        Some((c_wid, c_hei, grid_size))
+    }
+}
+
+/// Groups IntGrid cells by the level entity owning their layer and by cell value.
+fn cells_by_level<'a, T: MergedCell>(
+    cells: impl Iterator<Item = (&'a GridCoords, &'a Parent, &'a T)>,
+    level_grids: &LevelGrids,
+) -> HashMap<(Entity, T), HashSet<GridCoords>> {
+    let mut level_to_cells: HashMap<(Entity, T), HashSet<GridCoords>> = HashMap::new();
+    for (&grid_coords, parent, cell) in cells {
+        if let Some(level) = level_grids.level_of(parent) {
+            level_to_cells
+                .entry((level, *cell))
                 .or_default()
                 .insert(grid_coords);
         }
-    });
+    }
+    level_to_cells
+}
 
-    if !wall_query.is_empty() {
-        level_query.for_each(|(level_entity, level_handle)| {
-            if let Some(level_walls) = level_to_wall_locations.get(&level_entity) {
-                let level = levels.get(level_handle).expect("level should be loaded");
-
-                let LayerInstance {
-                    c_wid: width,
-                    c_hei: height,
-                    grid_size,
-                    ..
-                } = level
-                    .level
-                    .layer_instances
-                    .clone()
-                    .expect("level asset should have layers")[0];
-
-                let mut plate_stack: Vec<Vec<Plate>> = Vec::new();
-
-                for y in 0..height {
-                    let mut row_plates: Vec<Plate> = Vec::new();
-                    let mut plate_start = None;
-
-                    for x in 0..width + 1 {
-                        match (plate_start, level_walls.contains(&GridCoords { x, y })) {
-                            (Some(s), false) => {
-                                row_plates.push(Plate {
-                                    left: s,
-                                    right: x - 1,
-                                });
-                                plate_start = None;
-                            }
-                            (None, true) => plate_start = Some(x),
-                            _ => (),
-                        }
-                    }
+/// Spawns merged colliders for added cells. When cells are added to a spawned level, only the
+/// colliders touching them are despawned and their cells merged again. When cells are removed,
+/// only the colliders covering them are, so ground next to a broken cell stays in place.
+pub fn spawn_cell_collision<T: MergedCell>(
+    mut commands: Commands,
+    mut removed_cells: RemovedComponents<T>,
+    added_query: Query<(&GridCoords, &Parent), Added<T>>,
+    coords_query: Query<(&GridCoords, &Parent)>,
+    cell_query: Query<(&GridCoords, &Parent, &T)>,
+    collider_query: Query<(Entity, &Parent, &MergedCollider<T>)>,
+    level_grids: LevelGrids,
+) {
+    // removed cells of despawned levels are gone along with their colliders
+    let removed = removed_cells
+        .iter()
+        .filter_map(|entity| coords_query.get(entity).ok());
+
+    let mut level_to_changed: HashMap<Entity, HashSet<(GridCoords, bool)>> = HashMap::new();
+    for (&grid_coords, parent, added) in added_query
+        .iter()
+        .map(|(grid_coords, parent)| (grid_coords, parent, true))
+        .chain(removed.map(|(grid_coords, parent)| (grid_coords, parent, false)))
+    {
+        if let Some(level) = level_grids.level_of(parent) {
+            level_to_changed
+                .entry(level)
+                .or_default()
+                .insert((grid_coords, added));
+        }
+    }
+    if level_to_changed.is_empty() {
+        return;
+    }
+
+    let mut level_to_region: HashMap<Entity, HashSet<GridCoords>> = level_to_changed
+        .iter()
+        .map(|(&level, changed)| (level, changed.iter().map(|(cell, _)| *cell).collect()))
+        .collect();
+    for (collider_entity, parent, merged) in &collider_query {
+        let Some(changed) = level_to_changed.get(&parent.get()) else {
+            continue;
+        };
+        if changed.iter().any(|(grid_coords, added)| {
+            if *added {
+                merged.rect.touches(grid_coords)
+            } else {
+                merged.rect.contains(grid_coords)
+            }
+        }) {
+            commands.entity(collider_entity).despawn_recursive();
+            if let Some(region) = level_to_region.get_mut(&parent.get()) {
+                region.extend(merged.rect.cells());
+            }
+        }
+    }
 
-                    plate_stack.push(row_plates);
+    let region_cells = cell_query.iter().filter(|(grid_coords, parent, _)| {
+        level_grids
+            .level_of(parent)
+            .and_then(|level| level_to_region.get(&level))
+            .is_some_and(|region| region.contains(grid_coords))
+    });
+    // spawned in a fixed order, as the order colliders are added in affects the simulation
+    let mut level_to_cell_locations: Vec<_> = cells_by_level(region_cells, &level_grids)
+        .into_iter()
+        .collect();
+    level_to_cell_locations.sort_by_key(|((level_entity, _), level_cells)| {
+        let first_cell = level_cells
+            .iter()
+            .map(|grid_coords| (grid_coords.y, grid_coords.x))
+            .min();
+        (level_grids.iid(*level_entity), first_cell)
+    });
+
+    for ((level_entity, cell), level_cells) in level_to_cell_locations {
+        if let Some((width, height, grid_size)) = level_grids.grid(level_entity) {
+            commands.entity(level_entity).with_children(|level| {
+                for rect in merge_cells(&level_cells, width, height) {
+                    level.spawn((
+                        cell.rect_bundle(&rect, grid_size),
+                        MergedCollider::<T> {
+                            rect,
+                            marker: PhantomData,
+                        },
+                    ));
                 }
+            });
+        }
+    }
+}
+
+/// Sets friction and restitution of surfaces when they spawn and when the table is reloaded.
+pub fn apply_surface_properties(
+    mut commands: Commands,
+    assets: Res<AllAssets>,
+    surface_tables: Res<Assets<SurfaceTable>>,
+    mut asset_events: EventReader<AssetEvent<SurfaceTable>>,
+    surface_query: Query<(Entity, Ref<Surface>)>,
+) {
+    let reloaded = asset_events.iter().count() > 0;
+    if let Some(surface_table) = surface_tables.get(&assets.surfaces) {
+        for (entity, surface) in &surface_query {
+            if reloaded || surface.is_added() {
+                let properties = surface_table.get(*surface);
+                commands.entity(entity).insert((
+                    Friction::new(properties.friction),
+                    Restitution {
+                        coefficient: properties.restitution,
+                        combine_rule: CoefficientCombineRule::Max,
+                    },
+                ));
+            }
+        }
+    }
+}
 
-                let mut rect_builder: HashMap<Plate, Rect> = HashMap::new();
-                let mut prev_row: Vec<Plate> = Vec::new();
-                let mut wall_rects: Vec<Rect> = Vec::new();
+/// Splits ramp cells into straight runs along the same surface line and returns the outline of
+/// each run in cells. A run covers its cells plus the corners diagonally beneath them, which keeps
+/// it convex so the ramp surface is one edge without seams between cells.
+pub fn slope_runs(cells: &HashMap<GridCoords, Slope>) -> Vec<(Slope, Vec<Vec2>)> {
+    // surface lines keyed by rise and height at x = 0, both in half cells
+    let mut lines: BTreeMap<(i32, i32), Vec<(GridCoords, Slope)>> = BTreeMap::new();
+    for (&grid_coords, &slope) in cells {
+        let (rise, left) = slope.profile();
+        let intercept = grid_coords.y as f32 + left - rise * grid_coords.x as f32;
+        lines
+            .entry(((rise * 2.) as i32, (intercept * 2.) as i32))
+            .or_default()
+            .push((grid_coords, slope));
+    }
+
+    let mut runs = Vec::new();
+    for ((rise, intercept), mut line_cells) in lines {
+        let (rise, intercept) = (rise as f32 / 2., intercept as f32 / 2.);
+        let surface = |x: f32| intercept + rise * x;
+        line_cells.sort_by_key(|(grid_coords, _)| grid_coords.x);
+
+        let mut start = 0;
+        for end in 1..=line_cells.len() {
+            if end < line_cells.len() && line_cells[end].0.x == line_cells[end - 1].0.x + 1 {
+                continue;
+            }
+            let run = &line_cells[start..end];
+            let left = run[0].0.x as f32;
+            let right = run[run.len() - 1].0.x as f32 + 1.;
+            let floor = run
+                .iter()
+                .map(|(grid_coords, _)| grid_coords.y)
+                .min()
+                .unwrap_or(0) as f32;
+
+            let mut outline = vec![
+                Vec2::new(left, surface(left)),
+                Vec2::new(right, surface(right)),
+                Vec2::new(left, (surface(left) - 1.).max(floor)),
+                Vec2::new(right, (surface(right) - 1.).max(floor)),
+            ];
+            let floor_crossing = (floor + 1. - intercept) / rise;
+            if floor_crossing > left && floor_crossing < right {
+                outline.push(Vec2::new(floor_crossing, floor));
+            }
+            runs.push((run[0].1, outline));
+            start = end;
+        }
+    }
+    runs
+}
 
-                plate_stack.push(Vec::new());
+pub fn spawn_slope_collision(
+    mut commands: Commands,
+    slope_query: Query<(&GridCoords, &Parent, &Slope), Added<Slope>>,
+    level_grids: LevelGrids,
+) {
+    let mut level_to_slopes: HashMap<Entity, HashMap<GridCoords, Slope>> = HashMap::new();
+    for (&grid_coords, parent, &slope) in &slope_query {
+        if let Some(level) = level_grids.level_of(parent) {
+            level_to_slopes
+                .entry(level)
+                .or_default()
+                .insert(grid_coords, slope);
+        }
+    }
+    // spawned in a fixed order, as the order colliders are added in affects the simulation
+    let mut level_to_slopes: Vec<_> = level_to_slopes.into_iter().collect();
+    level_to_slopes.sort_by_key(|(level_entity, _)| level_grids.iid(*level_entity));
 
-                for (y, current_row) in plate_stack.into_iter().enumerate() {
-                    for prev_plate in &prev_row {
-                        if !current_row.contains(prev_plate) {
-                            if let Some(rect) = rect_builder.remove(prev_plate) {
-                                wall_rects.push(rect);
-                            }
+    for (level_entity, level_slopes) in level_to_slopes {
+        if let Some((_, _, grid_size)) = level_grids.grid(level_entity) {
+            commands.entity(level_entity).with_children(|level| {
+                for (slope, outline) in slope_runs(&level_slopes) {
+                    let points: Vec<Vec2> = outline
+                        .into_iter()
+                        .map(|point| point * grid_size as f32)
+                        .collect();
+                    // the first two outline points are the ends of the surface
+                    let surface = if points[0].y < points[1].y {
+                        RampSurface {
+                            low: points[0],
+                            high: points[1],
                         }
+                    } else {
+                        RampSurface {
+                            low: points[1],
+                            high: points[0],
+                        }
+                    };
+                    if let Some(collider) = Collider::convex_hull(&points) {
+                        let (rise, _) = slope.profile();
+                        level.spawn((
+                            collider,
+                            RigidBody::Fixed,
+                            // holds the player still on steep ramps, whatever its own friction
+                            Friction {
+                                coefficient: 1.,
+                                combine_rule: CoefficientCombineRule::Max,
+                            },
+                            SlopeCollider {
+                                normal: Vec2::new(-rise, 1.).normalize(),
+                                surface,
+                            },
+                            TransformBundle::default(),
+                        ));
                     }
-                    for plate in &current_row {
-                        rect_builder
-                            .entry(plate.clone())
-                            .and_modify(|e| e.top += 1)
-                            .or_insert(Rect {
-                                bottom: y as i32,
-                                top: y as i32,
-                                left: plate.left,
-                                right: plate.right,
-                            });
-                    }
-                    prev_row = current_row;
                 }
-
-                commands.entity(level_entity).with_children(|level| {
-                    for wall_rect in wall_rects {
-                        level
-                            .spawn_empty()
-                            .insert(Collider::cuboid(
-                                (wall_rect.right as f32 - wall_rect.left as f32 + 1.)
-                                    * grid_size as f32
-                                    / 2.,
-                                (wall_rect.top as f32 - wall_rect.bottom as f32 + 1.)
-                                    * grid_size as f32
-                                    / 2.,
-                            ))
-                            .insert(RigidBody::Fixed)
-                            .insert(Friction::new(1.0))
-                            .insert(Transform::from_xyz(
-                                (wall_rect.left + wall_rect.right + 1) as f32 * grid_size as f32
-                                    / 2.,
-                                (wall_rect.bottom + wall_rect.top + 1) as f32 * grid_size as f32
-                                    / 2.,
-                                0.,
-                            ))
-                            .insert(GlobalTransform::default());
-                    }
-                });
-            }
-        });
+            });
+        }
     }
 }
 
 pub fn spawn_sensors(
     mut commands: Commands,
-    contact_detectors: Query<(Entity, &Collider), Added<ContactDetection>>,
+    contact_detectors: Query<
+        (Entity, &Collider, Option<&ContactSensorConfig>),
+        Added<ContactDetection>,
+    >,
 ) {
-    for (entity, collider) in &contact_detectors {
-        if let Some(cuboid) = collider.as_cuboid() {
-            let Vec2 {
-                x: half_extents_x,
-                y: half_extents_y,
-            } = cuboid.half_extents();
-
-            let sensor_collider_left = Collider::cuboid(half_extents_x * 0.5, half_extents_y);
-            let sensor_translation_left = Vec3::new(-half_extents_x * 1.1, 0., 0.);
-
-            let sensor_collider_right = Collider::cuboid(half_extents_x * 0.5, half_extents_y);
-            let sensor_translation_right = Vec3::new(half_extents_x * 1.1, 0., 0.);
-
-            let sensor_collider_ground =
-                Collider::cuboid(half_extents_x * 0.9, half_extents_y / 2.);
-            let sensor_translation_ground = Vec3::new(0., -half_extents_y, 0.);
-
-            let sensor_collider_stable_left =
-                Collider::cuboid(half_extents_x * 0.125, half_extents_y * 0.5);
-            let sensor_translation_stable_left =
-                Vec3::new(-half_extents_x + 1., -half_extents_y, 0.);
-
-            let sensor_collider_stable_right =
-                Collider::cuboid(half_extents_x * 0.125, half_extents_y * 0.5);
-            let sensor_translation_stable_right =
-                Vec3::new(half_extents_x - 1., -half_extents_y, 0.);
-
-            commands.entity(entity).with_children(|builder| {
+    for (entity, collider, config) in &contact_detectors {
+        let config = config.cloned().unwrap_or_default();
+        let (centre, half_extents) = collider_extents(collider);
+
+        commands.entity(entity).with_children(|builder| {
+            if let Some(friction_scale) = config.friction_scale {
+                // scaled through the transform so rapier keeps the scale when syncing colliders
                 builder
                     .spawn_empty()
                     .insert(ActiveEvents::COLLISION_EVENTS)
-                    .insert(Collider::cuboid(
-                        half_extents_x * 1.02,
-                        half_extents_y * 0.99,
-                    ))
-                    .insert(Transform::from_translation(Vec3::new(0., 0., 0.)))
+                    .insert(collider.clone())
+                    .insert(Transform::from_scale(friction_scale.extend(1.)))
                     .insert(GlobalTransform::default())
                     .insert(Friction {
                         coefficient: 0.0,
                         combine_rule: CoefficientCombineRule::Min,
                     })
                     .insert(FrictionCollider);
+            }
+            for shape in &config.sensors {
+                let sensor_half_extents = half_extents * shape.half_extents;
+                let sensor_translation = centre + half_extents * shape.offset + shape.nudge;
                 builder
                     .spawn_empty()
                     .insert(ActiveEvents::COLLISION_EVENTS)
-                    .insert(sensor_collider_left)
-                    .insert(Sensor)
-                    .insert(Transform::from_translation(sensor_translation_left))
-                    .insert(GlobalTransform::default())
-                    .insert(ContactSensorLeft)
-                    .insert(ContactSensor {
-                        detecting_entity: entity,
-                        intersecting_entities: HashSet::new(),
-                    });
-                builder
-                    .spawn_empty()
-                    .insert(ActiveEvents::COLLISION_EVENTS)
-                    .insert(sensor_collider_right)
-                    .insert(Sensor)
-                    .insert(Transform::from_translation(sensor_translation_right))
-                    .insert(GlobalTransform::default())
-                    .insert(ContactSensorRight)
-                    .insert(ContactSensor {
-                        detecting_entity: entity,
-                        intersecting_entities: HashSet::new(),
-                    });
-                builder
-                    .spawn_empty()
-                    .insert(ActiveEvents::COLLISION_EVENTS)
-                    .insert(sensor_collider_ground)
-                    .insert(Sensor)
-                    .insert(Transform::from_translation(sensor_translation_ground))
-                    .insert(GlobalTransform::default())
-                    .insert(ContactSensorGround)
-                    .insert(ContactSensor {
-                        detecting_entity: entity,
-                        intersecting_entities: HashSet::new(),
-                    });
-                builder
-                    .spawn_empty()
-                    .insert(ActiveEvents::COLLISION_EVENTS)
-                    .insert(sensor_collider_stable_left)
-                    .insert(Sensor)
-                    .insert(Transform::from_translation(sensor_translation_stable_left))
-                    .insert(GlobalTransform::default())
-                    .insert(ContactSensorStableLeft)
-                    .insert(ContactSensor {
-                        detecting_entity: entity,
-                        intersecting_entities: HashSet::new(),
-                    });
-                builder
-                    .spawn_empty()
-                    .insert(ActiveEvents::COLLISION_EVENTS)
-                    .insert(sensor_collider_stable_right)
+                    .insert(Collider::cuboid(
+                        sensor_half_extents.x,
+                        sensor_half_extents.y,
+                    ))
                     .insert(Sensor)
-                    .insert(Transform::from_translation(sensor_translation_stable_right))
+                    .insert(Transform::from_translation(sensor_translation.extend(0.)))
                     .insert(GlobalTransform::default())
-                    .insert(ContactSensorStableRight)
                     .insert(ContactSensor {
+                        side: shape.side,
                         detecting_entity: entity,
                         intersecting_entities: HashSet::new(),
                     });
-            });
-        }
+            }
+        });
     }
 }
 
@@ -320,15 +990,14 @@ pub fn contact_detection(
                     }
                 }
             }
+            // the other collider may have been despawned already, such as a merged wall rebuilt
+            // around a broken cell, so it is removed whether or not it still has a collider
             CollisionEvent::Stopped(e1, e2, _) => {
-                if collidables.contains(*e1) {
-                    if let Ok(mut sensor) = contact_sensors.get_mut(*e2) {
-                        sensor.intersecting_entities.remove(e1);
-                    }
-                } else if collidables.contains(*e2) {
-                    if let Ok(mut sensor) = contact_sensors.get_mut(*e1) {
-                        sensor.intersecting_entities.remove(e2);
-                    }
+                if let Ok(mut sensor) = contact_sensors.get_mut(*e2) {
+                    sensor.intersecting_entities.remove(e1);
+                }
+                if let Ok(mut sensor) = contact_sensors.get_mut(*e1) {
+                    sensor.intersecting_entities.remove(e2);
                 }
             }
         }
@@ -336,40 +1005,333 @@ pub fn contact_detection(
 }
 
 pub fn update_contact_detectors(
+    mut wall_events: EventWriter<TouchedWall>,
+    mut stable_events: EventWriter<BecameStable>,
     mut contact_detectors: Query<&mut ContactDetection>,
-    ground_sensors: Query<
-        (
-            &ContactSensor,
-            Option<&ContactSensorLeft>,
-            Option<&ContactSensorRight>,
-            Option<&ContactSensorGround>,
-            Option<&ContactSensorStableLeft>,
-            Option<&ContactSensorStableRight>,
-        ),
-        Changed<ContactSensor>,
-    >,
+    non_wall_query: Query<(), Or<(With<OneWayPlatformCollider>, With<SlopeCollider>)>>,
+    surface_query: Query<&Surface>,
+    sensors: Query<&ContactSensor, Changed<ContactSensor>>,
 ) {
-    for (sensor, left, right, ground, stable_left, stable_right) in &ground_sensors {
+    // all contacts are recorded first, so walls are told apart from ground that changed this frame
+    for sensor in &sensors {
+        if let Ok(mut contact_detection) = contact_detectors.get_mut(sensor.detecting_entity) {
+            contact_detection
+                .contacts
+                .insert(sensor.side, sensor.intersecting_entities.clone());
+        }
+    }
+    for sensor in &sensors {
         if let Ok(mut contact_detection) = contact_detectors.get_mut(sensor.detecting_entity) {
-            if left.is_some() {
-                contact_detection.on_left = !sensor.intersecting_entities.is_empty();
+            // one-way platforms and ramps are never walls, and neither is the ground, which a hard
+            // landing can sink the side sensors into for a step
+            let ground = contact_detection.contacts.get(&ContactSide::Ground);
+            let touching_wall = sensor.intersecting_entities.iter().any(|entity| {
+                !non_wall_query.contains(*entity)
+                    && !ground.is_some_and(|ground| ground.contains(entity))
+            });
+            let surface = touched_surface(&sensor.intersecting_entities, &surface_query);
+            let was_stable = contact_detection.is_stable;
+            let touched = match sensor.side {
+                ContactSide::Left => touching_wall && !contact_detection.on_left,
+                ContactSide::Right => touching_wall && !contact_detection.on_right,
+                _ => false,
+            };
+            match sensor.side {
+                ContactSide::Left => {
+                    contact_detection.on_left = touching_wall;
+                    contact_detection.left_surface = surface;
+                }
+                ContactSide::Right => {
+                    contact_detection.on_right = touching_wall;
+                    contact_detection.right_surface = surface;
+                }
+                ContactSide::StableLeft => {
+                    contact_detection.stable_left = !sensor.intersecting_entities.is_empty();
+                    contact_detection.is_stable =
+                        contact_detection.stable_left && contact_detection.stable_right;
+                }
+                ContactSide::StableRight => {
+                    contact_detection.stable_right = !sensor.intersecting_entities.is_empty();
+                    contact_detection.is_stable =
+                        contact_detection.stable_left && contact_detection.stable_right;
+                }
+                // ground is handled by update_ground_detection
+                ContactSide::Ground => (),
+            }
+
+            if touched {
+                wall_events.send(TouchedWall {
+                    entity: sensor.detecting_entity,
+                    side: sensor.side,
+                });
             }
-            if right.is_some() {
-                contact_detection.on_right = !sensor.intersecting_entities.is_empty();
+            if contact_detection.is_stable && !was_stable {
+                stable_events.send(BecameStable {
+                    entity: sensor.detecting_entity,
+                });
+            }
+        }
+    }
+}
+
+/// What the ground under a detector can be made of.
+#[derive(SystemParam)]
+pub struct GroundQueries<'w, 's> {
+    platforms:
+        Query<'w, 's, (&'static GlobalTransform, &'static Collider), With<OneWayPlatformCollider>>,
+    slopes: Query<'w, 's, (&'static SlopeCollider, &'static GlobalTransform)>,
+    bodies: Query<'w, 's, (&'static RigidBody, &'static Velocity)>,
+    surfaces: Query<'w, 's, &'static Surface>,
+}
+
+/// Ground is re-checked every frame, since a one-way platform only counts while the detector is
+/// on top of it and not rising, rather than whenever the sensor overlaps it.
+pub fn update_ground_detection(
+    mut landed_events: EventWriter<Landed>,
+    mut left_ground_events: EventWriter<LeftGround>,
+    // velocity of each airborne detector during the previous frame, before any impact
+    mut fall_velocities: Local<HashMap<Entity, Vec2>>,
+    mut removed_detectors: RemovedComponents<ContactDetection>,
+    mut contact_detectors: Query<(
+        &mut ContactDetection,
+        &GlobalTransform,
+        &Collider,
+        Option<&Velocity>,
+    )>,
+    ground: GroundQueries,
+    sensors: Query<&ContactSensor>,
+) {
+    for entity in removed_detectors.iter() {
+        fall_velocities.remove(&entity);
+    }
+
+    for sensor in sensors
+        .iter()
+        .filter(|sensor| sensor.side == ContactSide::Ground)
+    {
+        if let Ok((mut contact_detection, transform, collider, velocity)) =
+            contact_detectors.get_mut(sensor.detecting_entity)
+        {
+            let (centre, half_extents) = collider_extents(collider);
+            let feet = transform.translation().y + centre.y - half_extents.y;
+            let rising = velocity.is_some_and(|velocity| velocity.linvel.y > RISING_SPEED);
+
+            let mut on_solid = false;
+            let mut on_platform = false;
+            for entity in &sensor.intersecting_entities {
+                match ground.platforms.get(*entity) {
+                    Ok((platform_transform, platform_collider)) => {
+                        let top = platform_transform.translation().y
+                            + platform_collider
+                                .as_cuboid()
+                                .map_or(0., |cuboid| cuboid.half_extents().y);
+                        on_platform |= !rising && feet >= top - ONE_WAY_TOLERANCE;
+                    }
+                    Err(_) => on_solid = true,
+                }
             }
-            if ground.is_some() {
-                contact_detection.on_ground = !sensor.intersecting_entities.is_empty();
+
+            let ground_surface = touched_surface(&sensor.intersecting_entities, &ground.surfaces);
+            let on_ground = on_solid || on_platform;
+            let on_flat = on_platform
+                || sensor.intersecting_entities.iter().any(|entity| {
+                    !ground.slopes.contains(*entity) && !ground.platforms.contains(*entity)
+                });
+            // ramps rising away from either side are ground ahead, so the detector starts up them
+            // before its corner catches on their foot
+            let ramps_ahead = [(ContactSide::Left, 1.), (ContactSide::Right, -1.)]
+                .into_iter()
+                .filter(|_| on_ground)
+                .flat_map(|(side, rising)| {
+                    contact_detection
+                        .touching(side)
+                        .filter_map(|entity| ground.slopes.get(entity).ok())
+                        .filter(move |(slope, _)| slope.normal.x * rising > 0.)
+                })
+                .collect::<Vec<_>>();
+            // the steepest ramp wins where a ramp meets flat ground at its foot, flat ground wins
+            // at its crest where the whole ramp is beneath the feet
+            let ramp = sensor
+                .intersecting_entities
+                .iter()
+                .filter_map(|entity| ground.slopes.get(*entity).ok())
+                .map(|slope| (slope, on_flat))
+                .chain(ramps_ahead.into_iter().map(|slope| (slope, true)))
+                .map(|((slope, slope_transform), beside_flat)| {
+                    let surface = slope
+                        .surface
+                        .translated(slope_transform.translation().truncate());
+                    (slope.normal, surface, beside_flat)
+                })
+                .filter(|(_, surface, beside_flat)| {
+                    !beside_flat || surface.high.y > feet + CREST_TOLERANCE
+                })
+                .map(|(normal, surface, _)| (normal, surface))
+                .min_by(|(a, _), (b, _)| a.y.total_cmp(&b.y));
+            let ground_normal = ramp.map_or(
+                if on_ground { Vec2::Y } else { Vec2::ZERO },
+                |(normal, _)| normal,
+            );
+            let ramp_surface = ramp.map(|(_, surface)| surface);
+
+            let ground_velocity = sensor
+                .intersecting_entities
+                .iter()
+                .filter_map(|entity| ground.bodies.get(*entity).ok())
+                .find(|(rigid_body, _)| {
+                    matches!(
+                        rigid_body,
+                        RigidBody::KinematicVelocityBased | RigidBody::KinematicPositionBased
+                    )
+                })
+                .map(|(_, velocity)| velocity.linvel);
+
+            let entity = sensor.detecting_entity;
+            if on_ground && !contact_detection.on_ground {
+                landed_events.send(Landed {
+                    entity,
+                    impact_velocity: fall_velocities.remove(&entity).unwrap_or_default(),
+                });
+            } else if !on_ground && contact_detection.on_ground {
+                left_ground_events.send(LeftGround { entity });
             }
-            if stable_left.is_some() {
-                contact_detection.stable_left = !sensor.intersecting_entities.is_empty();
-                contact_detection.is_stable =
-                    contact_detection.stable_left && contact_detection.stable_right;
+            if !on_ground {
+                fall_velocities.insert(
+                    entity,
+                    velocity.map_or(Vec2::ZERO, |velocity| velocity.linvel),
+                );
             }
-            if stable_right.is_some() {
-                contact_detection.stable_right = !sensor.intersecting_entities.is_empty();
-                contact_detection.is_stable =
-                    contact_detection.stable_left && contact_detection.stable_right;
+
+            if contact_detection.on_ground != on_ground
+                || contact_detection.on_platform != on_platform
+                || contact_detection.ground_surface != ground_surface
+                || contact_detection.ground_normal != ground_normal
+                || contact_detection.ramp_surface != ramp_surface
+                || contact_detection.riding != ground_velocity.is_some()
+                || contact_detection.ground_velocity != ground_velocity.unwrap_or_default()
+            {
+                contact_detection.on_ground = on_ground;
+                contact_detection.on_platform = on_platform;
+                contact_detection.ground_surface = ground_surface;
+                contact_detection.ground_normal = ground_normal;
+                contact_detection.ramp_surface = ramp_surface;
+                contact_detection.riding = ground_velocity.is_some();
+                contact_detection.ground_velocity = ground_velocity.unwrap_or_default();
             }
         }
     }
 }
+
+/// Surface among the touched entities, picking the last in declaration order when several
+/// surfaces are touched at once so the result does not depend on iteration order.
+fn touched_surface(entities: &HashSet<Entity>, surface_query: &Query<&Surface>) -> Option<Surface> {
+    entities
+        .iter()
+        .filter_map(|entity| surface_query.get(*entity).ok())
+        .max()
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Slope of each run with the left and right ends of its surface.
+    fn run_surfaces(cells: &[(i32, i32, Slope)]) -> Vec<(Slope, Vec2, Vec2)> {
+        let cells = cells
+            .iter()
+            .map(|&(x, y, slope)| (GridCoords::new(x, y), slope))
+            .collect();
+        slope_runs(&cells)
+            .into_iter()
+            .map(|(slope, outline)| (slope, outline[0], outline[1]))
+            .collect()
+    }
+
+    #[test]
+    fn steep_cells_on_one_diagonal_merge_into_one_run() {
+        assert_eq!(
+            run_surfaces(&[
+                (0, 0, Slope::UpRight),
+                (1, 1, Slope::UpRight),
+                (2, 2, Slope::UpRight),
+            ]),
+            vec![(Slope::UpRight, Vec2::new(0., 0.), Vec2::new(3., 3.))]
+        );
+        assert_eq!(
+            run_surfaces(&[
+                (4, 2, Slope::UpLeft),
+                (5, 1, Slope::UpLeft),
+                (6, 0, Slope::UpLeft),
+            ]),
+            vec![(Slope::UpLeft, Vec2::new(4., 3.), Vec2::new(7., 0.))]
+        );
+    }
+
+    #[test]
+    fn gentle_halves_merge_into_one_run() {
+        assert_eq!(
+            run_surfaces(&[
+                (0, 0, Slope::GentleUpRightLow),
+                (1, 0, Slope::GentleUpRightHigh),
+                (2, 1, Slope::GentleUpRightLow),
+                (3, 1, Slope::GentleUpRightHigh),
+            ]),
+            vec![(
+                Slope::GentleUpRightLow,
+                Vec2::new(0., 0.),
+                Vec2::new(4., 2.)
+            )]
+        );
+        assert_eq!(
+            run_surfaces(&[
+                (0, 1, Slope::GentleUpLeftHigh),
+                (1, 1, Slope::GentleUpLeftLow),
+                (2, 0, Slope::GentleUpLeftHigh),
+                (3, 0, Slope::GentleUpLeftLow),
+            ]),
+            vec![(
+                Slope::GentleUpLeftHigh,
+                Vec2::new(0., 2.),
+                Vec2::new(4., 0.)
+            )]
+        );
+    }
+
+    #[test]
+    fn runs_split_at_gaps_and_changes_of_direction() {
+        let gap = run_surfaces(&[(0, 0, Slope::UpRight), (2, 2, Slope::UpRight)]);
+        assert_eq!(
+            gap,
+            vec![
+                (Slope::UpRight, Vec2::new(0., 0.), Vec2::new(1., 1.)),
+                (Slope::UpRight, Vec2::new(2., 2.), Vec2::new(3., 3.)),
+            ]
+        );
+        let mut peak = run_surfaces(&[(0, 0, Slope::UpRight), (1, 0, Slope::UpLeft)]);
+        peak.sort_by(|a, b| a.1.x.total_cmp(&b.1.x));
+        assert_eq!(
+            peak,
+            vec![
+                (Slope::UpRight, Vec2::new(0., 0.), Vec2::new(1., 1.)),
+                (Slope::UpLeft, Vec2::new(1., 1.), Vec2::new(2., 0.)),
+            ]
+        );
+    }
+
+    #[test]
+    fn runs_stay_convex_down_to_the_lowest_cell() {
+        let cells = [(0, 0), (1, 1), (2, 2)]
+            .map(|(x, y)| (GridCoords::new(x, y), Slope::UpRight))
+            .into_iter()
+            .collect();
+        let runs = slope_runs(&cells);
+        assert_eq!(runs.len(), 1);
+        let outline = &runs[0].1;
+        // one cell beneath the surface, but never below the lowest cell
+        assert!(outline.contains(&Vec2::new(3., 2.)));
+        assert!(outline.contains(&Vec2::new(1., 0.)));
+        assert!(outline.iter().all(|point| point.y >= 0.));
+        assert!(Collider::convex_hull(outline).is_some());
+    }
+}
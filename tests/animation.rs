@@ -0,0 +1,31 @@
+//! Player animation on the real level without a window, see `movement.rs`.
+#![cfg(not(feature = "render"))]
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use platformer::{Action, AllAssets, Simulation};
+
+const FRAME: Duration = Duration::from_millis(20);
+
+#[test]
+fn running_plays_the_run_frames_of_the_kitty_sheet() {
+    let mut sim = Simulation::new();
+    sim.run(30);
+    let sheet = sim.app.world.resource::<AllAssets>().kitty_sheet.clone();
+    assert_eq!(*sim.player::<Handle<TextureAtlas>>(), sheet);
+
+    sim.press(Action::Right);
+    sim.advance(FRAME);
+    let mut frames = HashSet::new();
+    for _ in 0..15 {
+        sim.advance(FRAME);
+        frames.insert(sim.player::<TextureAtlasSprite>().index);
+    }
+    assert!(
+        frames.iter().all(|frame| (2..=5).contains(frame)),
+        "{frames:?}"
+    );
+    assert!(frames.len() > 1, "stuck on {frames:?}");
+}
@@ -0,0 +1,69 @@
+//! Camera effects and pausing run on the real level without a window, see `movement.rs`.
+#![cfg(not(feature = "render"))]
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use platformer::{Action, Hitstop, Paused, Simulation};
+
+const FRAME: Duration = Duration::from_millis(10);
+
+#[derive(Resource, Default)]
+struct FixedSteps(usize);
+
+fn count_fixed_steps(mut steps: ResMut<FixedSteps>) {
+    steps.0 += 1;
+}
+
+#[test]
+fn pausing_stops_fixed_steps_until_resumed() {
+    let mut sim = Simulation::new();
+    sim.run(30);
+    sim.app
+        .init_resource::<FixedSteps>()
+        .add_system(count_fixed_steps.in_schedule(CoreSchedule::FixedUpdate));
+
+    sim.press(Action::Pause);
+    sim.advance(FRAME);
+    sim.release(Action::Pause);
+    assert!(sim.app.world.resource::<Paused>().0);
+    // a hitstop ending while paused does not resume the game
+    sim.app.world.send_event(Hitstop { duration: 0.05 });
+    sim.advance(FRAME);
+    sim.app.world.resource_mut::<FixedSteps>().0 = 0;
+    for _ in 0..20 {
+        sim.advance(FRAME);
+    }
+    assert_eq!(sim.app.world.resource::<FixedSteps>().0, 0);
+
+    sim.press(Action::Pause);
+    sim.advance(FRAME);
+    sim.release(Action::Pause);
+    assert!(!sim.app.world.resource::<Paused>().0);
+    for _ in 0..10 {
+        sim.advance(FRAME);
+    }
+    assert!(sim.app.world.resource::<FixedSteps>().0 > 0);
+}
+
+#[test]
+fn hitstop_stops_fixed_steps_for_its_duration() {
+    let mut sim = Simulation::new();
+    sim.run(30);
+    sim.app
+        .init_resource::<FixedSteps>()
+        .add_system(count_fixed_steps.in_schedule(CoreSchedule::FixedUpdate));
+
+    sim.app.world.send_event(Hitstop { duration: 0.1 });
+    sim.advance(FRAME);
+    sim.app.world.resource_mut::<FixedSteps>().0 = 0;
+    for _ in 0..9 {
+        sim.advance(FRAME);
+    }
+    assert_eq!(sim.app.world.resource::<FixedSteps>().0, 0);
+
+    for _ in 0..10 {
+        sim.advance(FRAME);
+    }
+    assert!(sim.app.world.resource::<FixedSteps>().0 > 0);
+}
@@ -0,0 +1,45 @@
+//! Movement config hot reloading on the real level without a window, see `movement.rs`.
+#![cfg(not(feature = "render"))]
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use platformer::{Action, AllAssets, MovementConfig, Simulation};
+
+const MOVEMENT_CONFIG: &str = "assets/config/player.movement.ron";
+
+/// Swaps the loaded movement config for the asset file with `edit` applied, the way a hot
+/// reload of the edited file does.
+fn reload_movement_config(sim: &mut Simulation, edit: impl Fn(String) -> String) {
+    let source = std::fs::read_to_string(MOVEMENT_CONFIG).expect("movement config is readable");
+    let config: MovementConfig = ron::from_str(&edit(source)).expect("edited config parses");
+    let movement = sim.app.world.resource::<AllAssets>().movement.clone();
+    sim.app
+        .world
+        .resource_mut::<Assets<MovementConfig>>()
+        .set_untracked(movement, config);
+}
+
+#[test]
+fn editing_the_movement_config_changes_gravity_and_movement() {
+    let mut sim = Simulation::new();
+    sim.run(30);
+    assert_eq!(
+        sim.app.world.resource::<RapierConfiguration>().gravity,
+        Vec2::new(0., -2000.)
+    );
+
+    reload_movement_config(&mut sim, |source| {
+        source
+            .replace("gravity: 2000.0,", "gravity: 1500.0,")
+            .replace("run_speed: 250.0,", "run_speed: 100.0,")
+    });
+    // asset events are only sent at the end of the frame the asset changed in
+    sim.run(2);
+    assert_eq!(
+        sim.app.world.resource::<RapierConfiguration>().gravity,
+        Vec2::new(0., -1500.)
+    );
+
+    sim.hold(Action::Right, 10);
+    assert_eq!(sim.player::<Velocity>().linvel.x, 100.);
+}
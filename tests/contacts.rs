@@ -0,0 +1,210 @@
+//! Contact change events on the real level without a window, see `movement.rs`.
+#![cfg(not(feature = "render"))]
+
+use bevy::ecs::event::ManualEventReader;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use platformer::{
+    Action, BecameStable, ContactDetection, ContactSensorConfig, ContactSide, Landed, LeftGround,
+    SensorShape, Simulation, TouchedWall,
+};
+
+/// Centre of the player standing on the floor it spawns on.
+const SPAWN: Vec2 = Vec2::new(172., 152.);
+/// On the spawn floor, whose right edge is at x 264.
+const FLOOR_EDGE: f32 = 264.;
+/// On the ice floor at the bottom right of the level.
+const ICE_FLOOR: Vec2 = Vec2::new(392., 24.);
+/// Left side of the wall at the right end of the ice floor.
+const ICE_WALL: f32 = 488.;
+
+/// Contact events sent for the player, read after every step so none are missed. Landings are
+/// kept with the player's velocity at the end of the step before.
+#[derive(Default)]
+struct Seen {
+    landed: Vec<(Vec2, Vec2)>,
+    left_ground: usize,
+    touched_wall: Vec<ContactSide>,
+    became_stable: usize,
+}
+
+#[derive(Default)]
+struct Watcher {
+    landed: ManualEventReader<Landed>,
+    left_ground: ManualEventReader<LeftGround>,
+    touched_wall: ManualEventReader<TouchedWall>,
+    became_stable: ManualEventReader<BecameStable>,
+}
+
+impl Watcher {
+    /// Starts watching from the events sent so far, which are skipped.
+    fn new(sim: &Simulation) -> Self {
+        let world = &sim.app.world;
+        Watcher {
+            landed: world.resource::<Events<Landed>>().get_reader(),
+            left_ground: world.resource::<Events<LeftGround>>().get_reader(),
+            touched_wall: world.resource::<Events<TouchedWall>>().get_reader(),
+            became_stable: world.resource::<Events<BecameStable>>().get_reader(),
+        }
+    }
+
+    fn run(&mut self, sim: &mut Simulation, steps: usize) -> Seen {
+        let mut seen = Seen::default();
+        for _ in 0..steps {
+            let velocity = sim.player::<Velocity>().linvel;
+            sim.step();
+            let world = &sim.app.world;
+            let player = sim.player;
+            seen.landed.extend(
+                self.landed
+                    .iter(world.resource::<Events<Landed>>())
+                    .filter(|event| event.entity == player)
+                    .map(|event| (event.impact_velocity, velocity)),
+            );
+            seen.left_ground += self
+                .left_ground
+                .iter(world.resource::<Events<LeftGround>>())
+                .filter(|event| event.entity == player)
+                .count();
+            seen.touched_wall.extend(
+                self.touched_wall
+                    .iter(world.resource::<Events<TouchedWall>>())
+                    .filter(|event| event.entity == player)
+                    .map(|event| event.side),
+            );
+            seen.became_stable += self
+                .became_stable
+                .iter(world.resource::<Events<BecameStable>>())
+                .filter(|event| event.entity == player)
+                .count();
+        }
+        seen
+    }
+}
+
+fn settled() -> Simulation {
+    let mut sim = Simulation::new();
+    sim.run(30);
+    sim
+}
+
+#[test]
+fn dropping_onto_the_floor_lands_once_with_the_fall_velocity() {
+    let mut sim = settled();
+    sim.teleport(SPAWN + Vec2::new(0., 40.));
+    let mut watcher = Watcher::new(&sim);
+    let seen = watcher.run(&mut sim, 60);
+
+    assert_eq!(seen.left_ground, 1);
+    assert_eq!(seen.landed.len(), 1);
+    // the ground sensor reaches a little below the feet, so the landing is seen a step before
+    // the player has fallen the whole 40 px and would be going 400 px/s
+    let (impact, velocity) = seen.landed[0];
+    assert_eq!(impact, velocity);
+    assert!((-400. ..-300.).contains(&impact.y), "landed at {impact}");
+    assert_eq!(seen.became_stable, 1);
+    assert!(seen.touched_wall.is_empty());
+}
+
+#[test]
+fn walking_off_a_ledge_leaves_the_ground_once() {
+    let mut sim = settled();
+    let mut watcher = Watcher::new(&sim);
+    sim.press(Action::Right);
+    let seen = watcher.run(&mut sim, 30);
+    sim.release(Action::Right);
+
+    assert_eq!(seen.left_ground, 1);
+    assert!(seen.landed.is_empty());
+    assert!(!sim.player::<ContactDetection>().on_ground);
+}
+
+#[test]
+fn running_into_a_wall_touches_it_once_on_that_side() {
+    let mut sim = settled();
+    sim.teleport(ICE_FLOOR);
+    sim.run(30);
+    let mut watcher = Watcher::new(&sim);
+    sim.press(Action::Right);
+    let seen = watcher.run(&mut sim, 60);
+    sim.release(Action::Right);
+
+    assert_eq!(seen.touched_wall, vec![ContactSide::Right]);
+    assert!(sim.player::<ContactDetection>().on_right);
+    assert_eq!(seen.left_ground, 0);
+}
+
+#[test]
+fn standing_over_an_edge_is_not_stable_until_back_on_the_floor() {
+    let mut sim = settled();
+    // the right bottom corner hangs past the edge, the centre is still over the floor
+    sim.teleport(Vec2::new(FLOOR_EDGE - 2., SPAWN.y + 8.));
+    let mut watcher = Watcher::new(&sim);
+    let seen = watcher.run(&mut sim, 30);
+    assert_eq!(seen.landed.len(), 1);
+    assert_eq!(seen.became_stable, 0);
+    let contact_detection = sim.player::<ContactDetection>();
+    assert!(contact_detection.on_ground && !contact_detection.is_stable);
+
+    sim.press(Action::Left);
+    let seen = watcher.run(&mut sim, 10);
+    sim.release(Action::Left);
+    assert_eq!(seen.became_stable, 1);
+    assert!(sim.player::<ContactDetection>().is_stable);
+}
+
+#[test]
+fn a_ball_with_its_own_sensors_detects_the_ground_and_a_wall() {
+    let mut sim = settled();
+    let radius = 6.;
+    let sensor = |side, offset| SensorShape {
+        side,
+        half_extents: Vec2::splat(0.5),
+        offset,
+        nudge: Vec2::ZERO,
+    };
+    let ball = sim
+        .app
+        .world
+        .spawn((
+            Collider::ball(radius),
+            RigidBody::Dynamic,
+            LockedAxes::ROTATION_LOCKED,
+            Velocity::default(),
+            ContactDetection::default(),
+            ContactSensorConfig {
+                sensors: vec![
+                    sensor(ContactSide::Left, Vec2::new(-1.2, 0.)),
+                    sensor(ContactSide::Right, Vec2::new(1.2, 0.)),
+                    sensor(ContactSide::Ground, Vec2::new(0., -1.2)),
+                ],
+                friction_scale: None,
+            },
+            TransformBundle::from_transform(Transform::from_xyz(
+                ICE_WALL - radius - 0.5,
+                ICE_FLOOR.y,
+                0.,
+            )),
+        ))
+        .id();
+    sim.run(30);
+
+    // only the sensors are spawned, without a frictionless copy of the ball
+    assert_eq!(
+        sim.app
+            .world
+            .get::<Children>(ball)
+            .map(|children| children.len()),
+        Some(3)
+    );
+    let contact_detection = sim
+        .app
+        .world
+        .get::<ContactDetection>(ball)
+        .expect("ball has contact detection");
+    assert!(contact_detection.on_ground);
+    assert!(contact_detection.on_right && !contact_detection.on_left);
+    assert_eq!(contact_detection.touching(ContactSide::Ground).count(), 1);
+    assert_eq!(contact_detection.touching(ContactSide::Right).count(), 1);
+    assert_eq!(contact_detection.touching(ContactSide::Left).count(), 0);
+}
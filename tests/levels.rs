@@ -0,0 +1,142 @@
+//! Moving between levels on the real level file without a window, see `movement.rs`.
+#![cfg(not(feature = "render"))]
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use platformer::{Action, AllAssets, ContactDetection, LevelProgress, Simulation};
+
+/// On the ledge in the top right of the first level, left of its exit.
+const BEFORE_EXIT: Vec2 = Vec2::new(384., 216.);
+/// Where the entry of the second level puts the player.
+const SECOND_LEVEL_ENTRY: Vec2 = Vec2::new(576., 40.);
+/// Where the entry of the first level, next to its exit, puts the player.
+const FIRST_LEVEL_ENTRY: Vec2 = Vec2::new(424., 224.);
+
+const FRAME: Duration = Duration::from_millis(20);
+
+fn level_iids(sim: &Simulation) -> Vec<String> {
+    let handle = &sim.app.world.resource::<AllAssets>().level0;
+    sim.app
+        .world
+        .resource::<Assets<LdtkAsset>>()
+        .get(handle)
+        .expect("the level file has loaded")
+        .iter_levels()
+        .map(|level| level.iid.clone())
+        .collect()
+}
+
+fn spawned_level_iids(sim: &mut Simulation) -> Vec<String> {
+    let mut level_query = sim.app.world.query::<&Handle<LdtkLevel>>();
+    let levels = sim.app.world.resource::<Assets<LdtkLevel>>();
+    let mut iids: Vec<_> = level_query
+        .iter(&sim.app.world)
+        .filter_map(|handle| levels.get(handle))
+        .map(|level| level.level.iid.clone())
+        .collect();
+    iids.sort();
+    iids
+}
+
+fn camera_corner(sim: &mut Simulation) -> Vec2 {
+    let mut camera_query = sim.app.world.query_filtered::<&Transform, With<Camera2d>>();
+    camera_query.single(&sim.app.world).translation.truncate()
+}
+
+fn position(sim: &Simulation) -> Vec2 {
+    sim.player::<Transform>().translation.truncate()
+}
+
+/// Holds the action down until the player has been taken to another level.
+fn walk_through_exit(sim: &mut Simulation, action: Action) {
+    let start = sim.app.world.resource::<LevelProgress>().current.clone();
+    sim.press(action);
+    for _ in 0..60 {
+        sim.step();
+        if sim.app.world.resource::<LevelProgress>().current != start {
+            break;
+        }
+    }
+    sim.release(action);
+}
+
+#[test]
+fn level_exits_lead_to_their_entries_and_back() {
+    let mut sim = Simulation::new();
+    sim.run(30);
+    let iids = level_iids(&sim);
+    let (first, second) = (&iids[0], &iids[1]);
+    assert_eq!(
+        sim.app.world.resource::<LevelProgress>().current.as_ref(),
+        Some(first)
+    );
+
+    sim.teleport(BEFORE_EXIT);
+    sim.run(10);
+    walk_through_exit(&mut sim, Action::Right);
+    assert_eq!(
+        *sim.app.world.resource::<LevelSelection>(),
+        LevelSelection::Iid(second.clone())
+    );
+    sim.run(30);
+    let progress = sim.app.world.resource::<LevelProgress>();
+    assert_eq!(progress.current.as_ref(), Some(second));
+    assert!(progress.visited.contains(first) && progress.visited.contains(second));
+    // the second level's floor has spawned under the entry
+    assert!(sim.player::<ContactDetection>().on_ground);
+    let arrived = position(&sim);
+    assert!(
+        arrived.distance(SECOND_LEVEL_ENTRY) < 1.,
+        "arrived at {arrived}"
+    );
+
+    walk_through_exit(&mut sim, Action::Right);
+    assert_eq!(
+        *sim.app.world.resource::<LevelSelection>(),
+        LevelSelection::Iid(first.clone())
+    );
+    sim.run(30);
+    let progress = sim.app.world.resource::<LevelProgress>();
+    assert_eq!(progress.current.as_ref(), Some(first));
+    assert_eq!(progress.visited.len(), 2);
+    assert!(sim.player::<ContactDetection>().on_ground);
+    let arrived = position(&sim);
+    assert!(
+        arrived.distance(FIRST_LEVEL_ENTRY) < 1.,
+        "arrived at {arrived}"
+    );
+}
+
+#[test]
+fn neighbouring_levels_are_spawned_and_the_camera_moves_into_the_next_one() {
+    let mut sim = Simulation::new();
+    sim.run(30);
+    let mut iids = level_iids(&sim);
+    let second = iids[1].clone();
+    iids.sort();
+    // the second level lies right of the first, so both are spawned from the start
+    assert_eq!(spawned_level_iids(&mut sim), iids);
+    for _ in 0..30 {
+        sim.advance(FRAME);
+    }
+    // the first level ends at x 512, the view is 320 wide
+    let corner = camera_corner(&mut sim);
+    assert!(corner.x <= 192., "camera at {corner}");
+
+    sim.teleport(BEFORE_EXIT);
+    sim.run(10);
+    walk_through_exit(&mut sim, Action::Right);
+    assert_eq!(
+        sim.app.world.resource::<LevelProgress>().current.as_ref(),
+        Some(&second)
+    );
+    for _ in 0..120 {
+        sim.advance(FRAME);
+    }
+    assert_eq!(spawned_level_iids(&mut sim), iids);
+    // the second level covers x 512 to 768, narrower than the view, so the view is centred on it
+    let corner = camera_corner(&mut sim);
+    assert!((corner.x - 480.).abs() < 1., "camera at {corner}");
+}
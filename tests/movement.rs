@@ -0,0 +1,686 @@
+//! Movement scenarios run on the real level without a window. The LDtk renderer needs a GPU, so
+//! these only build with `cargo test --no-default-features --features test-harness`.
+#![cfg(not(feature = "render"))]
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use platformer::{
+    Action, AllAssets, CarriedVelocity, ContactDetection, LastSafeSpot, MovePerformed,
+    MovementConfig, MovingPlatform, PlayerState, RespawnMode, Simulation, Stamina,
+};
+
+/// Centre of the player standing on the floor it spawns on.
+const SPAWN: Vec2 = Vec2::new(172., 152.);
+/// Against the left wall of the shaft on the right of the level.
+const SHAFT_LEFT_WALL: Vec2 = Vec2::new(424., 120.);
+/// On the plateau at the top of the ramp left of the spawn.
+const RAMP_TOP: Vec2 = Vec2::new(120., 168.);
+/// Left of the steep hill in the second level, which rises to a plateau at y 136 and falls back
+/// down to the slab it stands on at y 112.
+const STEEP_HILL_LEFT: Vec2 = Vec2::new(543., 120.);
+/// Right of the steep hill in the second level.
+const STEEP_HILL_RIGHT: Vec2 = Vec2::new(636., 120.);
+/// Left of the gentle hill in the second level, which rises to a plateau at y 200 and falls back
+/// down to the slab it stands on at y 184.
+const GENTLE_HILL_LEFT: Vec2 = Vec2::new(543., 192.);
+/// Right of the gentle hill in the second level.
+const GENTLE_HILL_RIGHT: Vec2 = Vec2::new(660., 192.);
+/// On the crumbling cells left of the ramp.
+const CRUMBLING: Vec2 = Vec2::new(84., 161.);
+/// Above the cells that break under a ground pound, near the bottom of the level.
+const BREAKABLE: Vec2 = Vec2::new(312., 48.);
+/// On the spawn floor under the one-way platform right of the spawn, whose top is at y 176.
+const UNDER_PLATFORM: Vec2 = Vec2::new(228., 152.);
+/// On the ice floor at the bottom right of the level.
+const ICE_FLOOR: Vec2 = Vec2::new(392., 24.);
+/// Above the bouncy floor in the top middle of the level, whose top is at y 208.
+const ABOVE_BOUNCY: Vec2 = Vec2::new(344., 236.);
+/// Against the sticky upper half of the right wall of the shaft.
+const STICKY_WALL: Vec2 = Vec2::new(448., 150.);
+/// Against the icy lower half of the right wall of the shaft.
+const ICE_WALL: Vec2 = Vec2::new(448., 90.);
+/// Distance from the centre of the moving platform up to the centre of a player standing on it.
+const ABOVE_MOVING_PLATFORM: f32 = 12.;
+/// On the ledge left of the spikes, in the top left of the level.
+const BEFORE_SPIKES: Vec2 = Vec2::new(32., 128.);
+/// Near the edge of the floor over the lava pit, left of the middle of the level.
+const ABOVE_LAVA: Vec2 = Vec2::new(166., 96.);
+
+fn settled() -> Simulation {
+    let mut sim = Simulation::new();
+    sim.run(30);
+    sim
+}
+
+fn position(sim: &Simulation) -> Vec2 {
+    sim.player::<Transform>().translation.truncate()
+}
+
+#[test]
+fn player_lands_on_spawn_floor() {
+    let sim = settled();
+    assert!(sim.player::<ContactDetection>().on_ground);
+    assert!(sim.player::<Velocity>().linvel.y.abs() < 1.);
+    assert!(position(&sim).distance(SPAWN) < 1.);
+}
+
+#[test]
+fn standing_still_stays_idle() {
+    let mut sim = settled();
+    for _ in 0..90 {
+        sim.step();
+        assert_eq!(*sim.player::<PlayerState>(), PlayerState::Idle);
+    }
+}
+
+#[test]
+fn running_on_flat_ground_stays_running() {
+    let mut sim = settled();
+    sim.press(Action::Right);
+    sim.step();
+    for _ in 0..12 {
+        sim.step();
+        assert_eq!(*sim.player::<PlayerState>(), PlayerState::Run);
+    }
+}
+
+#[test]
+fn running_up_the_ramp_stays_on_the_ground() {
+    let mut sim = settled();
+    sim.press(Action::Left);
+    let mut last = position(&sim);
+    for _ in 0..16 {
+        sim.step();
+        assert_eq!(*sim.player::<PlayerState>(), PlayerState::Run);
+        assert!(sim.player::<ContactDetection>().on_ground);
+        let position = position(&sim);
+        assert!(position.x <= last.x, "moved back from {last} to {position}");
+        assert!(position.y < RAMP_TOP.y + 1., "launched to {position}");
+        last = position;
+    }
+    assert!(last.x < 136., "stopped at {last}");
+    assert!((last.y - RAMP_TOP.y).abs() < 1., "stopped at {last}");
+}
+
+#[test]
+fn running_down_the_ramp_stays_on_the_ground() {
+    let mut sim = settled();
+    sim.teleport(RAMP_TOP);
+    sim.run(10);
+    sim.press(Action::Right);
+    for _ in 0..16 {
+        sim.step();
+        assert_eq!(*sim.player::<PlayerState>(), PlayerState::Run);
+        assert!(sim.player::<ContactDetection>().on_ground);
+    }
+    let position = position(&sim);
+    assert!(position.x > 152., "stopped at {position}");
+    assert!((position.y - SPAWN.y).abs() < 1., "stopped at {position}");
+}
+
+/// Runs over a hill from one side to the other, checking that the player keeps to the ground
+/// all the way without being thrown off a ramp, and returns the highest point reached.
+fn run_over_hill(start: Vec2, end: Vec2, top: f32) -> f32 {
+    let mut sim = settled();
+    sim.teleport(start);
+    sim.run(10);
+    assert!(sim.player::<ContactDetection>().on_ground);
+    let action = if end.x > start.x {
+        Action::Right
+    } else {
+        Action::Left
+    };
+    sim.press(action);
+    let mut highest = f32::MIN;
+    for _ in 0..40 {
+        sim.step();
+        let position = position(&sim);
+        assert_eq!(
+            *sim.player::<PlayerState>(),
+            PlayerState::Run,
+            "at {position}"
+        );
+        assert!(sim.player::<ContactDetection>().on_ground, "at {position}");
+        assert!(position.y < top + 8. + 1., "launched to {position}");
+        highest = highest.max(position.y);
+        if (end.x - position.x) * (end.x - start.x) <= 0. {
+            break;
+        }
+    }
+    sim.release(action);
+    let position = position(&sim);
+    assert!(
+        (position.x - end.x) * (end.x - start.x) >= 0.,
+        "stopped at {position}"
+    );
+    assert!((position.y - end.y).abs() < 1., "stopped at {position}");
+    highest
+}
+
+#[test]
+fn running_over_the_steep_hill_stays_on_the_ground() {
+    let top = 136.;
+    for (start, end) in [
+        (STEEP_HILL_LEFT, STEEP_HILL_RIGHT),
+        (STEEP_HILL_RIGHT, STEEP_HILL_LEFT),
+    ] {
+        let highest = run_over_hill(start, end, top);
+        assert!((highest - (top + 8.)).abs() < 2., "only reached {highest}");
+    }
+}
+
+#[test]
+fn running_over_the_gentle_hill_stays_on_the_ground() {
+    let top = 200.;
+    for (start, end) in [
+        (GENTLE_HILL_LEFT, GENTLE_HILL_RIGHT),
+        (GENTLE_HILL_RIGHT, GENTLE_HILL_LEFT),
+    ] {
+        let highest = run_over_hill(start, end, top);
+        assert!((highest - (top + 8.)).abs() < 2., "only reached {highest}");
+    }
+}
+
+#[test]
+fn only_performed_jumps_are_reported_as_moves() {
+    let mut sim = settled();
+    let mut reader = sim
+        .app
+        .world
+        .resource::<Events<MovePerformed>>()
+        .get_reader();
+    let mut moves = Vec::new();
+    let mut step = |sim: &mut Simulation| {
+        sim.step();
+        let events = sim.app.world.resource::<Events<MovePerformed>>();
+        moves.extend(reader.iter(events).map(|performed| performed.state));
+    };
+
+    for _ in 0..30 {
+        step(&mut sim);
+    }
+    sim.press(Action::Jump);
+    step(&mut sim);
+    sim.release(Action::Jump);
+    for _ in 0..30 {
+        step(&mut sim);
+    }
+    assert_eq!(moves, vec![PlayerState::Jump]);
+}
+
+#[test]
+fn running_moves_at_run_speed() {
+    let mut sim = settled();
+    sim.hold(Action::Right, 20);
+    assert_eq!(sim.player::<Velocity>().linvel.x, 250.);
+    assert!(sim.player::<ContactDetection>().on_ground);
+    let travelled = position(&sim).x - SPAWN.x;
+    assert!((60. ..85.).contains(&travelled), "travelled {travelled}");
+}
+
+#[test]
+fn held_jump_rises_higher_than_tapped_jump() {
+    let apex = |jump_steps| {
+        let mut sim = settled();
+        sim.press(Action::Jump);
+        let mut apex = f32::MIN;
+        for step in 0..60 {
+            if step == jump_steps {
+                sim.release(Action::Jump);
+            }
+            sim.step();
+            apex = apex.max(position(&sim).y);
+        }
+        assert!(sim.player::<ContactDetection>().on_ground);
+        assert!((position(&sim).y - SPAWN.y).abs() < 1.);
+        apex - SPAWN.y
+    };
+
+    let held = apex(60);
+    let tapped = apex(3);
+    assert!((50. ..65.).contains(&held), "held jump rose {held}");
+    assert!(tapped < held - 20., "tapped jump rose {tapped}");
+}
+
+#[test]
+fn jumping_again_just_after_a_ground_jump_is_an_air_jump() {
+    let mut sim = settled();
+    // a low jump, so the ground sensor still touches the floor on the next press
+    let movement = sim.app.world.resource::<AllAssets>().movement.clone();
+    sim.app
+        .world
+        .resource_mut::<Assets<MovementConfig>>()
+        .get_mut(&movement)
+        .expect("movement config is loaded")
+        .ground_jump_speed = 200.;
+
+    sim.hold(Action::Jump, 1);
+    assert_eq!(*sim.player::<PlayerState>(), PlayerState::Jump);
+    sim.step();
+    sim.hold(Action::Jump, 1);
+    assert_eq!(*sim.player::<PlayerState>(), PlayerState::AirJump);
+}
+
+#[test]
+fn jumping_right_after_standing_still_is_a_ground_jump() {
+    let mut sim = settled();
+    sim.hold(Action::Jump, 1);
+    assert_eq!(*sim.player::<PlayerState>(), PlayerState::Jump);
+    assert_eq!(sim.player::<Velocity>().linvel.y, 500.);
+}
+
+#[test]
+fn jumping_right_after_running_up_the_ramp_is_a_ground_jump() {
+    // halfway up the ramp, then just after reaching the top
+    for steps in [6, 12] {
+        let mut sim = settled();
+        sim.hold(Action::Left, steps);
+        sim.hold(Action::Jump, 1);
+        assert_eq!(
+            *sim.player::<PlayerState>(),
+            PlayerState::Jump,
+            "after running for {steps} steps"
+        );
+        assert_eq!(sim.player::<Velocity>().linvel.y, 500.);
+    }
+}
+
+#[test]
+fn running_off_the_ledge_lands_on_the_block_below() {
+    let mut sim = settled();
+    sim.hold(Action::Right, 30);
+    sim.run(40);
+    let contact_detection = sim.player::<ContactDetection>();
+    assert!(contact_detection.on_ground);
+    // the block covers x 280 to 328, and its top is at y 104
+    let position = position(&sim);
+    assert!((280. ..336.).contains(&position.x), "landed at {position}");
+    assert!((position.y - 112.).abs() < 1., "landed at {position}");
+}
+
+#[test]
+fn crumbling_cells_drop_the_player_once_they_break() {
+    let mut sim = settled();
+    sim.teleport(CRUMBLING);
+    sim.run(10);
+    assert!(sim.player::<ContactDetection>().on_ground);
+    assert!(sim.player::<ContactDetection>().on_breakable);
+
+    let mut left_ground = false;
+    for _ in 0..40 {
+        sim.step();
+        left_ground |= !sim.player::<ContactDetection>().on_ground;
+    }
+    assert!(left_ground);
+    let position = position(&sim);
+    assert!(position.y < CRUMBLING.y - 16., "still at {position}");
+}
+
+#[test]
+fn ground_pound_breaks_the_cells_it_lands_on() {
+    let mut sim = settled();
+    sim.teleport(BREAKABLE);
+    sim.step();
+    sim.hold(Action::Down, 1);
+    assert_eq!(*sim.player::<PlayerState>(), PlayerState::GroundPound);
+
+    sim.run(30);
+    // standing on the cells would leave the player at y 32, the floor under them is 8 lower
+    assert!(sim.player::<ContactDetection>().on_ground);
+    let position = position(&sim);
+    assert!((position.y - 24.).abs() < 1., "landed at {position}");
+}
+
+#[test]
+fn falling_onto_a_platform_lands_on_it() {
+    let mut sim = settled();
+    sim.teleport(UNDER_PLATFORM + Vec2::new(0., 60.));
+    sim.run(30);
+    assert!(sim.player::<ContactDetection>().on_platform);
+    let position = position(&sim);
+    assert!((position.y - 184.).abs() < 1., "landed at {position}");
+}
+
+#[test]
+fn jumping_up_through_a_platform_lands_on_top_of_it() {
+    let mut sim = settled();
+    sim.teleport(UNDER_PLATFORM);
+    sim.run(10);
+    sim.hold(Action::Jump, 20);
+    sim.run(30);
+    assert!(sim.player::<ContactDetection>().on_platform);
+    let position = position(&sim);
+    assert!((position.y - 184.).abs() < 1., "landed at {position}");
+}
+
+#[test]
+fn pressing_down_drops_through_a_platform() {
+    let mut sim = settled();
+    sim.teleport(UNDER_PLATFORM + Vec2::new(0., 32.));
+    sim.run(30);
+    assert!(sim.player::<ContactDetection>().on_platform);
+
+    sim.hold(Action::Down, 1);
+    sim.run(30);
+    assert!(sim.player::<ContactDetection>().on_ground);
+    assert!(!sim.player::<ContactDetection>().on_platform);
+    let position = position(&sim);
+    assert!(
+        position.distance(UNDER_PLATFORM) < 1.,
+        "landed at {position}"
+    );
+}
+
+fn moving_platform(sim: &mut Simulation) -> (Vec2, Vec2) {
+    let (transform, velocity) = sim
+        .app
+        .world
+        .query_filtered::<(&Transform, &Velocity), With<MovingPlatform>>()
+        .single(&sim.app.world);
+    (transform.translation.truncate(), velocity.linvel)
+}
+
+fn board_moving_platform(sim: &mut Simulation) {
+    let (platform, _) = moving_platform(sim);
+    sim.teleport(platform + Vec2::new(0., 16.));
+    sim.run(30);
+    assert!(sim.player::<ContactDetection>().riding);
+}
+
+fn on_moving_platform(sim: &mut Simulation) -> bool {
+    let (platform, _) = moving_platform(sim);
+    let position = position(sim);
+    (position.x - platform.x).abs() < 16.
+        && (position.y - platform.y - ABOVE_MOVING_PLATFORM).abs() < 1.
+}
+
+#[test]
+fn falling_onto_the_moving_platform_lands_on_it() {
+    let mut sim = settled();
+    board_moving_platform(&mut sim);
+    assert!(sim.player::<ContactDetection>().on_ground);
+    assert!(on_moving_platform(&mut sim));
+}
+
+#[test]
+fn riding_the_moving_platform_through_a_full_ping_pong_carries_the_player() {
+    let mut sim = settled();
+    board_moving_platform(&mut sim);
+    let (start, _) = moving_platform(&mut sim);
+    let start_offset = position(&sim).x - start.x;
+    let (mut leftmost, mut rightmost) = (start.x, start.x);
+    let mut direction = 0.;
+    let mut reversals = 0;
+
+    // 56 px each way at 40 px/s, with half a second of waiting at either end
+    for _ in 0..270 {
+        let (_, platform_velocity) = moving_platform(&mut sim);
+        sim.step();
+        let contact_detection = sim.player::<ContactDetection>();
+        assert!(contact_detection.riding);
+        assert_eq!(contact_detection.ground_velocity, platform_velocity);
+        assert_eq!(**sim.player::<CarriedVelocity>(), platform_velocity);
+        let (platform, platform_velocity) = moving_platform(&mut sim);
+        let position = position(&sim);
+        assert!(
+            (position.x - platform.x - start_offset).abs() < 1.,
+            "left behind at {position} by the platform at {platform}"
+        );
+        assert!(on_moving_platform(&mut sim));
+
+        leftmost = leftmost.min(platform.x);
+        rightmost = rightmost.max(platform.x);
+        if platform_velocity.x != 0. {
+            if direction != 0. && platform_velocity.x.signum() != direction {
+                reversals += 1;
+            }
+            direction = platform_velocity.x.signum();
+        }
+    }
+
+    assert!(
+        (rightmost - leftmost - 56.).abs() < 0.1,
+        "rode {leftmost}..{rightmost}"
+    );
+    assert_eq!(reversals, 2);
+}
+
+#[test]
+fn riding_the_moving_platform_records_no_safe_spot() {
+    let mut sim = settled();
+    let safe_spot = **sim.player::<LastSafeSpot>();
+    assert!(safe_spot.truncate().distance(SPAWN) < 1.);
+    board_moving_platform(&mut sim);
+    // long enough for the platform to stop at an end, where the player stands still on it
+    for _ in 0..120 {
+        sim.step();
+        assert!(sim.player::<ContactDetection>().riding);
+        assert_eq!(**sim.player::<LastSafeSpot>(), safe_spot);
+    }
+}
+
+#[test]
+fn dashing_moves_level_at_dash_speed_then_slows_down() {
+    let mut sim = settled();
+    let mut reader = sim
+        .app
+        .world
+        .resource::<Events<MovePerformed>>()
+        .get_reader();
+    sim.hold(Action::Dash, 1);
+    assert_eq!(*sim.player::<PlayerState>(), PlayerState::Dash);
+    let events = sim.app.world.resource::<Events<MovePerformed>>();
+    let moves: Vec<_> = reader
+        .iter(events)
+        .map(|performed| performed.state)
+        .collect();
+    assert_eq!(moves, vec![PlayerState::Dash]);
+
+    for _ in 0..6 {
+        sim.step();
+        assert_eq!(*sim.player::<PlayerState>(), PlayerState::Dash);
+        assert_eq!(sim.player::<Velocity>().linvel.x, 450.);
+        assert!((position(&sim).y - SPAWN.y).abs() < 1.);
+    }
+    sim.run(30);
+    assert_eq!(*sim.player::<PlayerState>(), PlayerState::Idle);
+    let travelled = position(&sim).x - SPAWN.x;
+    assert!((60. ..120.).contains(&travelled), "travelled {travelled}");
+}
+
+#[test]
+fn dashing_in_the_air_is_allowed_once_until_landing() {
+    let mut sim = settled();
+    sim.press(Action::Jump);
+    sim.run(10);
+    sim.hold(Action::Dash, 1);
+    assert_eq!(*sim.player::<PlayerState>(), PlayerState::Dash);
+    assert_eq!(sim.player::<Velocity>().linvel, Vec2::new(450., 0.));
+    sim.run(10);
+    sim.hold(Action::Dash, 1);
+    assert_ne!(*sim.player::<PlayerState>(), PlayerState::Dash);
+
+    sim.release(Action::Jump);
+    sim.run(60);
+    assert!(sim.player::<ContactDetection>().on_ground);
+    sim.hold(Action::Dash, 1);
+    assert_eq!(*sim.player::<PlayerState>(), PlayerState::Dash);
+}
+
+#[test]
+fn wall_jump_pushes_away_from_left_wall() {
+    let mut sim = settled();
+    sim.teleport(SHAFT_LEFT_WALL);
+    sim.hold(Action::Left, 10);
+    assert!(sim.player::<ContactDetection>().on_left);
+    assert_eq!(*sim.player::<PlayerState>(), PlayerState::WallSlide);
+    assert_eq!(sim.player::<Velocity>().linvel.y, -15.);
+
+    let start = position(&sim);
+    sim.press(Action::Jump);
+    sim.step();
+    assert_eq!(*sim.player::<PlayerState>(), PlayerState::WallJump);
+    assert_eq!(sim.player::<Velocity>().linvel, Vec2::new(300., 400.));
+    sim.run(4);
+    let moved = position(&sim) - start;
+    assert!(moved.x > 10. && moved.y > 10., "moved {moved}");
+    assert!(!sim.player::<ContactDetection>().on_left);
+}
+
+#[test]
+fn running_on_ice_keeps_sliding_after_letting_go() {
+    let speed_after_letting_go = |start| {
+        let mut sim = settled();
+        sim.teleport(start);
+        sim.run(10);
+        sim.hold(Action::Right, 6);
+        sim.run(5);
+        sim.player::<Velocity>().linvel.x
+    };
+    let on_normal = speed_after_letting_go(SPAWN);
+    assert!(on_normal < 150., "still moving at {on_normal}");
+    let on_ice = speed_after_letting_go(ICE_FLOOR);
+    assert!(on_ice > 240., "slowed down to {on_ice}");
+}
+
+#[test]
+fn landing_on_bouncy_ground_bounces_back_up() {
+    let mut sim = settled();
+    sim.teleport(ABOVE_BOUNCY);
+    let mut landed = false;
+    let mut bounced = false;
+    for _ in 0..20 {
+        sim.step();
+        let on_ground = sim.player::<ContactDetection>().on_ground;
+        bounced |= landed && !on_ground && sim.player::<Velocity>().linvel.y > 0.;
+        landed |= on_ground;
+    }
+    assert!(bounced);
+}
+
+#[test]
+fn wall_slide_speed_depends_on_the_wall_surface() {
+    let mut sim = settled();
+    sim.teleport(STICKY_WALL);
+    sim.hold(Action::Right, 10);
+    assert_eq!(*sim.player::<PlayerState>(), PlayerState::WallSlide);
+    assert_eq!(sim.player::<Velocity>().linvel.y, 0.);
+
+    let mut sim = settled();
+    sim.teleport(ICE_WALL);
+    sim.hold(Action::Right, 10);
+    assert_eq!(*sim.player::<PlayerState>(), PlayerState::WallSlide);
+    assert_eq!(sim.player::<Velocity>().linvel.y, -120.);
+}
+
+#[test]
+fn ice_walls_cannot_be_jumped_off() {
+    let jump_off = |start| {
+        let mut sim = settled();
+        sim.teleport(start);
+        sim.press(Action::Right);
+        sim.run(10);
+        sim.hold(Action::Jump, 1);
+        *sim.player::<PlayerState>()
+    };
+    assert_eq!(jump_off(STICKY_WALL), PlayerState::WallJump);
+    assert_eq!(jump_off(ICE_WALL), PlayerState::AirJump);
+}
+
+#[test]
+fn falling_out_of_the_level_respawns_at_checkpoint() {
+    let mut sim = settled();
+    sim.teleport(Vec2::new(SPAWN.x, -200.));
+    sim.step();
+    assert_eq!(*sim.player::<PlayerState>(), PlayerState::Dead);
+    sim.run(60);
+    assert_ne!(*sim.player::<PlayerState>(), PlayerState::Dead);
+    assert!(position(&sim).distance(SPAWN) < 1.);
+}
+
+#[test]
+fn the_player_spawns_and_respawns_with_full_stamina() {
+    let mut sim = Simulation::new();
+    let movement = sim.app.world.resource::<AllAssets>().movement.clone();
+    let max_stamina = sim
+        .app
+        .world
+        .resource::<Assets<MovementConfig>>()
+        .get(&movement)
+        .expect("movement config is loaded")
+        .max_stamina;
+    // no fixed step has run yet, so the player has not touched the ground
+    assert_eq!(**sim.player::<Stamina>(), max_stamina);
+
+    sim.run(30);
+    sim.hold(Action::Jump, 1);
+    sim.run(5);
+    sim.hold(Action::Jump, 1);
+    assert_eq!(*sim.player::<PlayerState>(), PlayerState::AirJump);
+    assert_eq!(**sim.player::<Stamina>(), max_stamina - 1);
+
+    sim.teleport(Vec2::new(SPAWN.x, -200.));
+    sim.step();
+    assert_eq!(*sim.player::<PlayerState>(), PlayerState::Dead);
+    // movement does not run on the step that respawns the player, so the ground has not
+    // refilled the stamina yet
+    for _ in 0..60 {
+        sim.step();
+        if *sim.player::<PlayerState>() != PlayerState::Dead {
+            break;
+        }
+    }
+    assert_ne!(*sim.player::<PlayerState>(), PlayerState::Dead);
+    assert_eq!(**sim.player::<Stamina>(), max_stamina);
+}
+
+#[test]
+fn touching_spikes_respawns_at_checkpoint() {
+    let mut sim = settled();
+    sim.teleport(BEFORE_SPIKES);
+    sim.run(30);
+    sim.press(Action::Right);
+    let mut died = false;
+    for _ in 0..30 {
+        sim.step();
+        died |= *sim.player::<PlayerState>() == PlayerState::Dead;
+    }
+    sim.release(Action::Right);
+    assert!(died);
+    sim.run(60);
+    assert_ne!(*sim.player::<PlayerState>(), PlayerState::Dead);
+    assert!(
+        position(&sim).distance(SPAWN) < 1.,
+        "respawned at {}",
+        position(&sim)
+    );
+}
+
+#[test]
+fn falling_into_lava_respawns_at_last_safe_spot() {
+    let mut sim = settled();
+    sim.app.insert_resource(RespawnMode::LastSafeSpot);
+    sim.teleport(ABOVE_LAVA);
+    sim.run(30);
+    sim.press(Action::Right);
+    let mut died = false;
+    for _ in 0..60 {
+        sim.step();
+        died |= *sim.player::<PlayerState>() == PlayerState::Dead;
+    }
+    sim.release(Action::Right);
+    assert!(died);
+    sim.run(60);
+    assert_ne!(*sim.player::<PlayerState>(), PlayerState::Dead);
+    // the last spot the player stood still on is the floor at the edge of the pit, which ends
+    // at x 176
+    let position = position(&sim);
+    assert!(
+        (position.y - ABOVE_LAVA.y).abs() < 1.,
+        "respawned at {position}"
+    );
+    assert!(
+        (ABOVE_LAVA.x..184.).contains(&position.x),
+        "respawned at {position}"
+    );
+}
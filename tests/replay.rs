@@ -0,0 +1,78 @@
+//! Recording runs and playing them back on the real level without a window, see `movement.rs`.
+#![cfg(not(feature = "render"))]
+
+use std::path::PathBuf;
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use platformer::{Action, GameRng, Replay, ReplayMode, Simulation};
+use rand::Rng;
+
+/// A replay file of its own for each test, since tests run in parallel.
+fn replay_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("platformer-{}-{name}.ron", std::process::id()))
+}
+
+fn rng_draws(sim: &mut Simulation) -> Vec<u64> {
+    let mut rng = sim.app.world.resource_mut::<GameRng>();
+    (0..4).map(|_| rng.gen()).collect()
+}
+
+/// Fixed steps run by [scripted_input].
+const STEPS: usize = 90;
+
+/// Runs and jumps about over the spawn floor's edge and back.
+fn scripted_input(sim: &mut Simulation) {
+    sim.hold(Action::Right, 20);
+    sim.press(Action::Jump);
+    sim.hold(Action::Right, 15);
+    sim.release(Action::Jump);
+    sim.hold(Action::Left, 25);
+    sim.hold(Action::Jump, 10);
+    sim.run(20);
+}
+
+#[test]
+fn a_recorded_run_plays_back_exactly() {
+    let path = replay_path("round-trip");
+
+    let mut sim = Simulation::with_replay_mode(ReplayMode::Record(path.clone()));
+    scripted_input(&mut sim);
+    let recorded_transform = *sim.player::<Transform>();
+    let recorded_draws = rng_draws(&mut sim);
+    // the replay is saved when the game exits
+    sim.app.world.send_event(AppExit);
+    sim.app.update();
+
+    let mut sim = Simulation::with_replay_mode(ReplayMode::Replay(path.clone()));
+    sim.run(STEPS);
+    let replayed_transform = *sim.player::<Transform>();
+    let replayed_draws = rng_draws(&mut sim);
+    std::fs::remove_file(&path).ok();
+
+    assert_ne!(
+        recorded_transform.translation,
+        Simulation::new().player::<Transform>().translation,
+        "the scripted input should move the player"
+    );
+    assert_eq!(replayed_transform, recorded_transform);
+    assert_eq!(replayed_draws, recorded_draws);
+}
+
+#[test]
+fn a_replay_starting_in_an_unknown_level_is_dropped() {
+    let path = replay_path("unknown-level");
+    let mut replay = Replay::default();
+    replay.header.level = "no-such-level".to_string();
+    replay.save(&path);
+
+    let sim = Simulation::with_replay_mode(ReplayMode::Replay(path.clone()));
+    std::fs::remove_file(&path).ok();
+
+    // the player still spawns, in the first level
+    assert_eq!(
+        sim.player::<Transform>().translation,
+        Simulation::new().player::<Transform>().translation
+    );
+    assert_eq!(*sim.app.world.resource::<ReplayMode>(), ReplayMode::Off);
+}